@@ -1,3 +1,61 @@
+//! Proc macros for the FTL crate. Use them via the re-exports of the `ftl`.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod schema;
+
+/// Derives the `ftl::Schema` from the type definition.
+///
+/// Structs with named fields become objects, where fields of the `Option<_>` type
+/// or with the `#[serde(default)]` are not required. Enums with only unit variants become string enums of the variant names.
+/// Doc comments are used as descriptions.
+///
+/// Unit enums also implement the `ftl::schema::SchemaKey`, so the maps keyed by them
+/// list the variant names as the permitted keys.
+///
+/// Other enums with the data are externally tagged like the serde default,
+/// so the unit variants are their names and the struct and newtype variants are
+/// the objects with the single property of the variant name, like `{ "Name": { ...fields } }`.
+///
+/// Enums with the `#[serde(tag = "...")]` become the `oneOf` of objects
+/// with the tag property as the discriminator.
+///
+/// The `#[serde(untagged)]` enums and variants, and the `#[serde(content = "...")]`
+/// on the enums with data are rejected, as their schemas would not match the wire format.
+///
+/// Enums with the `#[non_exhaustive]` also accept the unknown variants,
+/// so the clients tolerate the variants added later.
+///
+/// Types which refer to themselves are named components, and the fields refer to them
+/// with the `$ref`, so the recursion terminates.
+///
+/// Type parameters used in the fields get the `Schema` bound. Recursive generic types
+/// are named after their parameters, like the `Tree_u32` for the `Tree<u32>`.
+///
+/// The `#[serde(rename = "...")]` on fields and variants and the `#[serde(rename_all = "...")]`
+/// on containers and struct variants are applied to the names, to match the wire format.
+///
+/// # Attributes
+///
+/// - `#[schema(repr = "int")]` on the unit-only enum describes it as its integer discriminants,
+///   to match the `serde_repr` style encodings.
+/// - `#[schema(transparent)]` on the struct with exactly one field reuses the schema of the field
+///   as is, to match the `#[serde(transparent)]`.
+/// - `#[schema(additional = T)]` on the struct describes the keys other than its fields
+///   as the `additionalProperties` of `T`, to match the `#[serde(flatten)]` map of `T` values.
+///   The flattened fields are left out of the properties.
+/// - `#[schema(null_example)]` on the `Option` field shows the `null` as its example,
+///   instead of the example of the inner type.
+#[proc_macro_derive(Schema, attributes(schema))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    schema::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,910 @@
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{
+    parenthesized, parse_quote, Attribute, Data, DataEnum, DeriveInput, Error, Expr, ExprLit,
+    ExprUnary, Field, Fields, FieldsNamed, Generics, Lit, LitStr, Result, Token, Type, UnOp,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repr {
+    String,
+    Int,
+}
+
+struct ContainerAttrs {
+    repr: Repr,
+    /// `#[schema(transparent)]` on the single-field structs.
+    transparent: bool,
+    /// `#[schema(additional = T)]` on the structs with the flattened map of `T` values.
+    additional: Option<Type>,
+}
+
+struct FieldAttrs {
+    /// `#[schema(null_example)]` on the `Option` fields.
+    null_example: bool,
+}
+
+/// Serde attributes which affect the wire format.
+#[derive(Default)]
+struct SerdeAttrs {
+    tag: Option<String>,
+    /// `#[serde(content = "...")]` of the adjacently tagged enum.
+    content: Option<String>,
+    /// `#[serde(untagged)]` on the enum or the variant.
+    untagged: bool,
+    /// `#[serde(other)]` on the catch-all variant.
+    other: bool,
+    rename: Option<String>,
+    rename_all: Option<RenameRule>,
+    /// `#[serde(default)]` on the field or the whole struct, which makes it optional.
+    default: bool,
+    /// `#[serde(flatten)]` on the field.
+    flatten: bool,
+}
+
+/// Rejects the enum representations the derived schemas don't describe,
+/// which are the untagged ones and the adjacently tagged ones with data.
+fn check_enum_repr(input: &DeriveInput, data: &DataEnum, serde: &SerdeAttrs) -> Result<()> {
+    if serde.untagged {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`#[serde(untagged)]` enums are not supported",
+        ));
+    }
+    if serde.content.is_some() && !is_unit_only(data) {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`#[serde(content)]` is not supported on the enums with data",
+        ));
+    }
+    for variant in &data.variants {
+        if SerdeAttrs::parse(&variant.attrs)?.untagged {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                "`#[serde(untagged)]` variants are not supported",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Case conversions of the `#[serde(rename_all = "...")]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let attrs = ContainerAttrs::parse(&input.attrs)?;
+    let serde = SerdeAttrs::parse(&input.attrs)?;
+    if attrs.transparent {
+        return derive_transparent(&input, &attrs);
+    }
+
+    let title = input.ident.to_string();
+    let description = option_str(doc_string(&input.attrs));
+
+    let non_exhaustive = input
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("non_exhaustive"));
+
+    if let Data::Enum(data) = &input.data {
+        check_enum_repr(&input, data, &serde)?;
+    }
+
+    if attrs.additional.is_some() && !matches!(&input.data, Data::Struct(_)) {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`additional` is only supported on structs",
+        ));
+    }
+
+    let (schema, components) = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) if attrs.repr == Repr::String => derive_object(
+                &title,
+                &description,
+                fields,
+                &serde,
+                attrs.additional.as_ref(),
+            )?,
+            Fields::Named(_) => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "`repr` is only supported on enums",
+                ))
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        Data::Enum(data) => match &serde.tag {
+            Some(tag) if attrs.repr == Repr::String => derive_tagged_enum(
+                &title,
+                &description,
+                data,
+                tag,
+                serde.rename_all,
+                non_exhaustive,
+            )?,
+            Some(_) => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "`repr` is not supported on the tagged enums",
+                ))
+            }
+            None if attrs.repr == Repr::String && !is_unit_only(data) => {
+                derive_external_enum(&title, &description, data, serde.rename_all, non_exhaustive)?
+            }
+            None => derive_enum(
+                &title,
+                &description,
+                data,
+                attrs.repr,
+                serde.rename_all,
+                non_exhaustive,
+            )?,
+        },
+        Data::Union(_) => return Err(Error::new_spanned(&input.ident, "unions are not supported")),
+    };
+
+    let ident = &input.ident;
+    let generics = schema_generics(&input);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let params: Vec<&Ident> = input
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect();
+    let schema_name = if !is_recursive(&input) {
+        quote!()
+    } else if params.is_empty() {
+        quote! {
+            fn schema_name() -> ::std::option::Option<::std::borrow::Cow<'static, str>> {
+                ::std::option::Option::Some(#title.into())
+            }
+        }
+    } else {
+        quote! {
+            fn schema_name() -> ::std::option::Option<::std::borrow::Cow<'static, str>> {
+                ::std::option::Option::Some(
+                    ::ftl::schema::derive::generic_name(
+                        #title,
+                        &[#(::ftl::schema::derive::param_name::<#params>()),*],
+                    )
+                    .into(),
+                )
+            }
+        }
+    };
+
+    let register_components = if components.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn register_components(registry: &mut ::ftl::schema::SchemaRegistry) {
+                #components
+            }
+        }
+    };
+
+    // Unit enums serialized as strings can be the map keys.
+    let schema_key = match &input.data {
+        Data::Enum(data)
+            if serde.tag.is_none() && attrs.repr == Repr::String && is_unit_only(data) =>
+        {
+            quote! {
+                impl #impl_generics ::ftl::schema::SchemaKey for #ident #ty_generics #where_clause {}
+            }
+        }
+        _ => quote!(),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::ftl::Schema for #ident #ty_generics #where_clause {
+            fn schema() -> ::ftl::schema::derive::oa::Schema {
+                #schema
+            }
+
+            #schema_name
+
+            #register_components
+        }
+
+        #schema_key
+    })
+}
+
+/// Transparent newtypes share every part of the schema with their only field,
+/// including the component name.
+fn derive_transparent(input: &DeriveInput, attrs: &ContainerAttrs) -> Result<TokenStream> {
+    let field = match &input.data {
+        Data::Struct(data) if data.fields.len() == 1 => data.fields.iter().next().unwrap(),
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "`transparent` is only supported on structs with exactly one field",
+            ))
+        }
+    };
+    if attrs.repr != Repr::String {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`repr` is only supported on enums",
+        ));
+    }
+
+    let ident = &input.ident;
+    let ty = &field.ty;
+    let generics = schema_generics(input);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::ftl::Schema for #ident #ty_generics #where_clause {
+            fn schema() -> ::ftl::schema::derive::oa::Schema {
+                <#ty as ::ftl::Schema>::schema()
+            }
+
+            fn schema_name() -> ::std::option::Option<::std::borrow::Cow<'static, str>> {
+                <#ty as ::ftl::Schema>::schema_name()
+            }
+
+            fn register_components(registry: &mut ::ftl::schema::SchemaRegistry) {
+                <#ty as ::ftl::Schema>::register_components(registry)
+            }
+        }
+    })
+}
+
+/// With the `additional`, the flattened fields are described by the `additionalProperties`
+/// of the `additional` type instead of the properties.
+fn derive_object(
+    title: &str,
+    description: &TokenStream,
+    fields: &FieldsNamed,
+    container: &SerdeAttrs,
+    additional: Option<&Type>,
+) -> Result<(TokenStream, TokenStream)> {
+    let additional = match additional {
+        Some(additional) => additional,
+        None => {
+            let (properties, components) = derive_fields(fields, container)?;
+            let schema = quote! {
+                ::ftl::schema::derive::object(#title, #description, vec![#(#properties),*])
+            };
+            return Ok((schema, quote!(#(#components)*)));
+        }
+    };
+
+    let mut known = fields.clone();
+    known.named = Default::default();
+    for field in &fields.named {
+        if !SerdeAttrs::parse(&field.attrs)?.flatten {
+            known.named.push(field.clone());
+        }
+    }
+    if known.named.len() == fields.named.len() {
+        return Err(Error::new_spanned(
+            additional,
+            "`additional` requires a `#[serde(flatten)]` field",
+        ));
+    }
+
+    let (properties, mut components) = derive_fields(&known, container)?;
+    components.push(quote! {
+        registry.register::<#additional>();
+    });
+    let schema = quote! {
+        ::ftl::schema::derive::object_with_additional::<#additional>(
+            #title,
+            #description,
+            vec![#(#properties),*],
+        )
+    };
+
+    Ok((schema, quote!(#(#components)*)))
+}
+
+/// Fields with the `Option` type or the `#[serde(default)]` are not required.
+fn derive_fields(
+    fields: &FieldsNamed,
+    container: &SerdeAttrs,
+) -> Result<(Vec<TokenStream>, Vec<TokenStream>)> {
+    let mut properties = vec![];
+    let mut components = vec![];
+
+    for field in &fields.named {
+        let ident = field
+            .ident
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let serde = SerdeAttrs::parse(&field.attrs)?;
+        let name = match serde.rename {
+            Some(name) => name,
+            None => container
+                .rename_all
+                .map_or(ident.clone(), |rule| rule.apply_to_field(&ident)),
+        };
+        let ty = &field.ty;
+        let optional = is_option(ty);
+        let required = !optional && !serde.default && !container.default;
+        let field_description = option_str(doc_string(&field.attrs));
+
+        let null_example = if FieldAttrs::parse(&field.attrs)?.null_example {
+            if !optional {
+                return Err(Error::new_spanned(
+                    ty,
+                    "`null_example` is only supported on the `Option` fields",
+                ));
+            }
+            quote!(.null_example())
+        } else {
+            quote!()
+        };
+
+        properties.push(quote! {
+            ::ftl::schema::derive::Field::new::<#ty>(#name, #required, #field_description)
+                #null_example
+        });
+        components.push(quote! {
+            registry.register::<#ty>();
+        });
+    }
+
+    Ok((properties, components))
+}
+
+/// Internally tagged enums become the `oneOf` of objects, with the tag property
+/// as the discriminator.
+fn derive_tagged_enum(
+    title: &str,
+    description: &TokenStream,
+    data: &DataEnum,
+    tag: &str,
+    rename_all: Option<RenameRule>,
+    non_exhaustive: bool,
+) -> Result<(TokenStream, TokenStream)> {
+    let mut variants = vec![];
+    let mut components = vec![];
+
+    for variant in &data.variants {
+        let serde = SerdeAttrs::parse(&variant.attrs)?;
+        let name = variant_name(&variant.ident, &serde, rename_all);
+        let variant_description = option_str(doc_string(&variant.attrs));
+        let properties = match &variant.fields {
+            Fields::Unit => vec![],
+            Fields::Named(fields) => {
+                let (properties, variant_components) = derive_fields(fields, &serde)?;
+                components.extend(variant_components);
+                properties
+            }
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "tuple variants are not supported on the tagged enums",
+                ))
+            }
+        };
+
+        variants.push(quote! {
+            (#name, #variant_description, vec![#(#properties),*])
+        });
+    }
+
+    let union = if non_exhaustive {
+        quote!(open_tagged_union)
+    } else {
+        quote!(tagged_union)
+    };
+    let schema = quote! {
+        ::ftl::schema::derive::#union(#title, #description, #tag, vec![#(#variants),*])
+    };
+
+    Ok((schema, quote!(#(#components)*)))
+}
+
+/// Externally tagged enums with the data become the `oneOf` of the variant names
+/// for the unit variants and the single property objects for the others,
+/// like `{ "Name": { ...fields } }` and `{ "Name": value }`.
+fn derive_external_enum(
+    title: &str,
+    description: &TokenStream,
+    data: &DataEnum,
+    rename_all: Option<RenameRule>,
+    non_exhaustive: bool,
+) -> Result<(TokenStream, TokenStream)> {
+    let mut arms = vec![];
+    let mut components = vec![];
+    let mut has_other = false;
+
+    for variant in &data.variants {
+        let serde = SerdeAttrs::parse(&variant.attrs)?;
+        if serde.other {
+            has_other = true;
+            continue;
+        }
+
+        let name = variant_name(&variant.ident, &serde, rename_all);
+        let variant_description = option_str(doc_string(&variant.attrs));
+        arms.push(match &variant.fields {
+            Fields::Unit => quote! {
+                ::ftl::schema::derive::unit_variant(#name, #variant_description)
+            },
+            Fields::Named(fields) => {
+                let (properties, variant_components) = derive_fields(fields, &serde)?;
+                components.extend(variant_components);
+                quote! {
+                    ::ftl::schema::derive::struct_variant(
+                        #name,
+                        #variant_description,
+                        vec![#(#properties),*],
+                    )
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                components.push(quote! {
+                    registry.register::<#ty>();
+                });
+                quote! {
+                    ::ftl::schema::derive::newtype_variant(
+                        #variant_description,
+                        ::ftl::schema::derive::Field::new::<#ty>(#name, true, None),
+                    )
+                }
+            }
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "tuple variants with more than one field are not supported",
+                ))
+            }
+        });
+    }
+
+    let union = if has_other || non_exhaustive {
+        quote!(open_external_union)
+    } else {
+        quote!(external_union)
+    };
+    let schema = quote! {
+        ::ftl::schema::derive::#union(#title, #description, vec![#(#arms),*])
+    };
+
+    Ok((schema, quote!(#(#components)*)))
+}
+
+fn derive_enum(
+    title: &str,
+    description: &TokenStream,
+    data: &DataEnum,
+    repr: Repr,
+    rename_all: Option<RenameRule>,
+    non_exhaustive: bool,
+) -> Result<(TokenStream, TokenStream)> {
+    let mut names = vec![];
+    let mut discriminants = vec![];
+    let mut next = 0i64;
+    let mut has_other = false;
+
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "only unit variants are supported",
+            ));
+        }
+
+        let value = match &variant.discriminant {
+            Some((_, expr)) => discriminant(expr)?,
+            None => next,
+        };
+        next = value.wrapping_add(1);
+
+        let serde = SerdeAttrs::parse(&variant.attrs)?;
+        if serde.other {
+            has_other = true;
+            continue;
+        }
+
+        names.push(variant_name(&variant.ident, &serde, rename_all));
+        discriminants.push(value);
+    }
+
+    let schema = match repr {
+        Repr::String if has_other || non_exhaustive => quote! {
+            ::ftl::schema::derive::open_string_enum(#title, #description, &[#(#names),*])
+        },
+        Repr::String => quote! {
+            ::ftl::schema::derive::string_enum(#title, #description, &[#(#names),*])
+        },
+        Repr::Int if non_exhaustive => quote! {
+            ::ftl::schema::derive::open_integer_enum(
+                #title,
+                #description,
+                &[#((#names, #discriminants)),*],
+            )
+        },
+        Repr::Int => quote! {
+            ::ftl::schema::derive::integer_enum(
+                #title,
+                #description,
+                &[#((#names, #discriminants)),*],
+            )
+        },
+    };
+
+    Ok((schema, quote!()))
+}
+
+impl ContainerAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = ContainerAttrs {
+            repr: Repr::String,
+            transparent: false,
+            additional: None,
+        };
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("schema")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("repr") {
+                    let repr: LitStr = meta.value()?.parse()?;
+                    parsed.repr = match &*repr.value() {
+                        "string" => Repr::String,
+                        "int" => Repr::Int,
+                        _ => return Err(meta.error("expected `string` or `int`")),
+                    };
+                    Ok(())
+                } else if meta.path.is_ident("transparent") {
+                    parsed.transparent = true;
+                    Ok(())
+                } else if meta.path.is_ident("additional") {
+                    parsed.additional = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown schema attribute"))
+                }
+            })?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = FieldAttrs {
+            null_example: false,
+        };
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("schema")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("null_example") {
+                    parsed.null_example = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown schema attribute"))
+                }
+            })?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = SerdeAttrs::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    let tag: LitStr = meta.value()?.parse()?;
+                    parsed.tag = Some(tag.value());
+                    Ok(())
+                } else if meta.path.is_ident("content") {
+                    let content: LitStr = meta.value()?.parse()?;
+                    parsed.content = Some(content.value());
+                    Ok(())
+                } else if meta.path.is_ident("untagged") {
+                    parsed.untagged = true;
+                    Ok(())
+                } else if meta.path.is_ident("other") {
+                    parsed.other = true;
+                    Ok(())
+                } else if meta.path.is_ident("rename") && meta.input.peek(Token![=]) {
+                    let rename: LitStr = meta.value()?.parse()?;
+                    parsed.rename = Some(rename.value());
+                    Ok(())
+                } else if meta.path.is_ident("rename_all") && meta.input.peek(Token![=]) {
+                    let rule: LitStr = meta.value()?.parse()?;
+                    parsed.rename_all = Some(RenameRule::parse(&rule)?);
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    parsed.default = true;
+                    skip_meta(meta)
+                } else if meta.path.is_ident("flatten") {
+                    parsed.flatten = true;
+                    Ok(())
+                } else {
+                    skip_meta(meta)
+                }
+            })?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl RenameRule {
+    fn parse(rule: &LitStr) -> Result<Self> {
+        Ok(match &*rule.value() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return Err(Error::new_spanned(rule, "unknown rename rule")),
+        })
+    }
+
+    /// Converts the `PascalCase` variant name, like the serde does.
+    fn apply_to_variant(self, variant: &str) -> String {
+        let snake = || {
+            let mut snake = String::new();
+            for (i, ch) in variant.char_indices() {
+                if i > 0 && ch.is_uppercase() {
+                    snake.push('_');
+                }
+                snake.push(ch.to_ascii_lowercase());
+            }
+            snake
+        };
+
+        match self {
+            Self::Lower => variant.to_ascii_lowercase(),
+            Self::Upper => variant.to_ascii_uppercase(),
+            Self::Pascal => variant.to_owned(),
+            Self::Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            Self::Snake => snake(),
+            Self::ScreamingSnake => snake().to_ascii_uppercase(),
+            Self::Kebab => snake().replace('_', "-"),
+            Self::ScreamingKebab => snake().to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+
+    /// Converts the `snake_case` field name, like the serde does.
+    fn apply_to_field(self, field: &str) -> String {
+        let pascal = || {
+            let mut pascal = String::new();
+            let mut capitalize = true;
+            for ch in field.chars() {
+                if ch == '_' {
+                    capitalize = true;
+                } else if capitalize {
+                    pascal.push(ch.to_ascii_uppercase());
+                    capitalize = false;
+                } else {
+                    pascal.push(ch);
+                }
+            }
+            pascal
+        };
+
+        match self {
+            Self::Lower | Self::Snake => field.to_owned(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal => pascal(),
+            Self::Camel => {
+                let pascal = pascal();
+                pascal[..1].to_ascii_lowercase() + &pascal[1..]
+            }
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+/// Name of the variant on the wire, after the `rename` and the `rename_all` of the enum.
+fn variant_name(ident: &syn::Ident, serde: &SerdeAttrs, rename_all: Option<RenameRule>) -> String {
+    let ident = ident.to_string();
+
+    match (&serde.rename, rename_all) {
+        (Some(name), _) => name.clone(),
+        (None, Some(rule)) => rule.apply_to_variant(&ident),
+        (None, None) => ident,
+    }
+}
+
+/// Skips the serde attribute which doesn't affect the schema.
+fn skip_meta(meta: ParseNestedMeta<'_>) -> Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        parenthesized!(content in meta.input);
+        content.parse::<TokenStream>()?;
+    }
+
+    Ok(())
+}
+
+fn discriminant(expr: &Expr) -> Result<i64> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        }) => int.base10_parse(),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => Ok(-discriminant(expr)?),
+        _ => Err(Error::new_spanned(
+            expr,
+            "discriminant should be an integer literal",
+        )),
+    }
+}
+
+/// Whether any field refers to the type itself. Such types are named components,
+/// so the fields can refer to them with the `$ref` instead of the infinite inlining.
+fn is_recursive(input: &DeriveInput) -> bool {
+    all_fields(input).iter().any(|field| {
+        let ty = &field.ty;
+        mentions(quote!(#ty), &input.ident)
+    })
+}
+
+/// Adds the `Schema` bound to the type parameters used in the fields.
+fn schema_generics(input: &DeriveInput) -> Generics {
+    let fields = all_fields(input);
+    let mut generics = input.generics.clone();
+    let used: Vec<Ident> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .filter(|param| {
+            fields.iter().any(|field| {
+                let ty = &field.ty;
+                mentions(quote!(#ty), param)
+            })
+        })
+        .collect();
+
+    let where_clause = generics.make_where_clause();
+    for param in used {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::ftl::Schema));
+    }
+
+    generics
+}
+
+fn is_unit_only(data: &DataEnum) -> bool {
+    data.variants
+        .iter()
+        .all(|variant| matches!(variant.fields, Fields::Unit))
+}
+
+fn all_fields(input: &DeriveInput) -> Vec<&Field> {
+    match &input.data {
+        Data::Struct(data) => data.fields.iter().collect(),
+        Data::Enum(data) => data.variants.iter().flat_map(|v| &v.fields).collect(),
+        Data::Union(_) => vec![],
+    }
+}
+
+fn mentions(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(name) => name == *ident || name == "Self",
+        TokenTree::Group(group) => mentions(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Concatenates the `///` doc comments.
+fn doc_string(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta.require_name_value().ok()?.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(doc), ..
+            }) => Some(doc.value().trim().to_owned()),
+            _ => None,
+        })
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+fn option_str(value: Option<String>) -> TokenStream {
+    match value {
+        Some(value) => quote!(::std::option::Option::Some(#value)),
+        None => quote!(::std::option::Option::None),
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+#[test]
+fn reject_untagged_and_adjacent_enums() {
+    let error = |input: DeriveInput| derive(input).unwrap_err().to_string();
+
+    assert_eq!(
+        error(parse_quote! {
+            #[serde(untagged)]
+            enum Value { Int(i64), Text(String) }
+        }),
+        "`#[serde(untagged)]` enums are not supported"
+    );
+    assert_eq!(
+        error(parse_quote! {
+            #[serde(tag = "t", content = "c")]
+            enum Shape { Circle { radius: f64 }, Empty }
+        }),
+        "`#[serde(content)]` is not supported on the enums with data"
+    );
+    assert_eq!(
+        error(parse_quote! {
+            enum Value { Known { id: u64 }, #[serde(untagged)] Raw(String) }
+        }),
+        "`#[serde(untagged)]` variants are not supported"
+    );
+
+    let unit_only: DeriveInput = parse_quote! {
+        #[serde(tag = "t", content = "c")]
+        enum Level { Low, High }
+    };
+    assert!(derive(unit_only).is_ok());
+}
+
+#[test]
+fn reject_additional_without_flatten() {
+    let input: DeriveInput = parse_quote! {
+        #[schema(additional = String)]
+        struct Labels { name: String }
+    };
+    assert_eq!(
+        derive(input).unwrap_err().to_string(),
+        "`additional` requires a `#[serde(flatten)]` field"
+    );
+
+    let input: DeriveInput = parse_quote! {
+        #[schema(additional = String)]
+        struct Labels { name: String, #[serde(flatten)] rest: HashMap<String, String> }
+    };
+    assert!(derive(input).is_ok());
+}
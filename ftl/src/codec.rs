@@ -0,0 +1,181 @@
+//! JSON encoding and decoding of the request and response bodies.
+
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{BaseError, InvalidParameter};
+use crate::BoxError;
+
+/// How strictly the JSON request bodies are accepted. Everything is accepted by default.
+///
+/// Set it for the whole service with the [`Builder::expect_json`], and override it
+/// for the routes of a router with the [`Router::expect_json`].
+///
+/// [`Builder::expect_json`]: crate::service::Builder::expect_json
+/// [`Router::expect_json`]: crate::router::Router::expect_json
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpectJson {
+    /// Rejects the fields the request type doesn't know,
+    /// even if the type itself ignores them.
+    pub deny_unknown_fields: bool,
+    /// Rejects the non-empty bodies without the JSON `Content-Type`
+    /// with `415 Unsupported Media Type`.
+    pub require_content_type: bool,
+}
+
+impl ExpectJson {
+    /// Denies both the unknown fields and the bodies of the other media types.
+    pub fn strict() -> Self {
+        ExpectJson {
+            deny_unknown_fields: true,
+            require_content_type: true,
+        }
+    }
+}
+
+/// Returns `true` for the `application/json` and the `+json` suffixed media types.
+pub fn is_json_content_type(value: &HeaderValue) -> bool {
+    let media_type = value
+        .to_str()
+        .unwrap_or_default()
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    media_type == "application/json"
+        || media_type.starts_with("application/") && media_type.ends_with("+json")
+}
+
+/// Parses the request body as JSON.
+///
+/// On failure the path to the offending field is reported
+/// in the `body` list of the [`BaseError::InvalidParameter`], like `outer.items[0].name`.
+pub fn from_json<T: DeserializeOwned>(body: &str) -> Result<T, BaseError> {
+    from_json_with(body, &ExpectJson::default())
+}
+
+/// Parses the request body as JSON like the [`from_json`], with the `expect`ed strictness.
+///
+/// Unknown fields are reported by their paths, if denied.
+pub fn from_json_with<T: DeserializeOwned>(
+    body: &str,
+    expect: &ExpectJson,
+) -> Result<T, BaseError> {
+    let de = &mut serde_json::Deserializer::from_str(body);
+    let mut unknown = vec![];
+    let mut track = |path: serde_ignored::Path<'_>| unknown.push(path.to_string());
+    let de = serde_ignored::Deserializer::new(de, &mut track);
+
+    let value = serde_path_to_error::deserialize(de).map_err(|err| {
+        invalid_body(vec![InvalidParameter {
+            name: err.path().to_string().into(),
+            value: None,
+            reason: Some(err.inner().to_string()),
+        }])
+    })?;
+
+    if expect.deny_unknown_fields && !unknown.is_empty() {
+        return Err(invalid_body(
+            unknown
+                .into_iter()
+                .map(|path| InvalidParameter {
+                    name: path.into(),
+                    value: None,
+                    reason: Some("unknown field".into()),
+                })
+                .collect(),
+        ));
+    }
+
+    Ok(value)
+}
+
+/// Error of the empty body where the type requires one.
+pub(crate) fn body_required() -> BaseError {
+    invalid_body(vec![InvalidParameter {
+        name: ".".into(),
+        value: None,
+        reason: Some("request body is required".into()),
+    }])
+}
+
+fn invalid_body(body: Vec<InvalidParameter>) -> BaseError {
+    BaseError::InvalidParameter {
+        query: vec![],
+        header: vec![],
+        body,
+    }
+}
+
+/// Serializes the value as the JSON response body with the given status.
+pub fn json_response<T: Serialize + ?Sized>(
+    status: StatusCode,
+    value: &T,
+) -> Result<Response<String>, BoxError> {
+    let mut resp = Response::new(serde_json::to_string(value)?);
+    *resp.status_mut() = status;
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(resp)
+}
+
+#[test]
+fn nested_type_error_reports_path() {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Outer {
+        #[allow(dead_code)]
+        items: Vec<Inner>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Inner {
+        #[allow(dead_code)]
+        count: u32,
+    }
+
+    let err = from_json::<Outer>(r#"{"items": [{"count": 1}, {"count": "two"}]}"#).unwrap_err();
+    assert_eq!(crate::Error::status(&err), http::StatusCode::BAD_REQUEST);
+
+    match err {
+        BaseError::InvalidParameter { body, .. } => {
+            assert_eq!(body.len(), 1);
+            assert_eq!(body[0].name, "items[1].count");
+            assert_eq!(body[0].value, None);
+            assert!(body[0].reason.as_ref().unwrap().contains("invalid type"));
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_fields_are_denied_by_path() {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Outer {
+        inner: Inner,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Inner {
+        count: u32,
+    }
+
+    let body = r#"{"inner": {"count": 1, "extra": true}}"#;
+    let outer = from_json::<Outer>(body).unwrap();
+    assert_eq!(outer.inner.count, 1);
+
+    match from_json_with::<Outer>(body, &ExpectJson::strict()).unwrap_err() {
+        BaseError::InvalidParameter { body, .. } => {
+            assert_eq!(body.len(), 1);
+            assert_eq!(body[0].name, "inner.extra");
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
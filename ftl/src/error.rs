@@ -2,6 +2,8 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
 
+use http::header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+use http::Response;
 use hyper::StatusCode;
 use indexmap::IndexMap;
 use openapiv3::{self as oa, Schema};
@@ -15,28 +17,103 @@ use crate::BoxError;
 pub trait Error: FtlSchema {
     fn status(&self) -> StatusCode;
 
+    /// Additional headers of the error response, like the `WWW-Authenticate`.
+    fn headers(&self) -> HeaderMap {
+        HeaderMap::new()
+    }
+
     fn error_schema() -> ErrorSchema;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ErrorSchema {
     pub default_schema: Option<Schema>,
     pub schemas: HashMap<StatusCode, Schema>,
 }
 
+impl ErrorSchema {
+    /// Unions the schemas of two error types, for the error which aggregates both.
+    ///
+    /// Schemas of the same status, as well as the two default schemas,
+    /// are combined into the `oneOf` unless they're equal.
+    pub fn merge(mut self, other: ErrorSchema) -> ErrorSchema {
+        for (status, schema) in other.schemas {
+            let merged = match self.schemas.remove(&status) {
+                Some(existing) => combine(existing, schema),
+                None => schema,
+            };
+            self.schemas.insert(status, merged);
+        }
+
+        self.default_schema = match (self.default_schema, other.default_schema) {
+            (Some(existing), Some(schema)) => Some(combine(existing, schema)),
+            (existing, schema) => existing.or(schema),
+        };
+
+        self
+    }
+}
+
+fn combine(left: Schema, right: Schema) -> Schema {
+    if left == right {
+        return left;
+    }
+
+    let mut variants = Vec::new();
+    for schema in [left, right] {
+        match schema {
+            Schema {
+                schema_data,
+                schema_kind: oa::SchemaKind::OneOf { one_of },
+            } if schema_data == Default::default() => variants.extend(one_of),
+            schema => variants.push(oa::ReferenceOr::Item(schema)),
+        }
+    }
+
+    Schema {
+        schema_data: Default::default(),
+        schema_kind: oa::SchemaKind::OneOf { one_of: variants },
+    }
+}
+
+/// Renders the error as the JSON response with its status code.
+pub fn error_response<E: Error>(error: &E) -> Response<String> {
+    let status = error.status();
+
+    let mut resp = crate::codec::json_response(status, error).unwrap_or_else(|_| {
+        let mut resp = Response::new(String::new());
+        *resp.status_mut() = status;
+        resp
+    });
+    resp.headers_mut().extend(error.headers());
+
+    resp
+}
+
 #[derive(Debug, Serialize, Deserialize, thiserror::Error)]
 #[non_exhaustive]
 pub enum BaseError {
+    #[error("403 Forbidden")]
+    Forbidden,
     #[error("404 Not Found")]
     NotFound,
     #[error("405 Method Not Allowed")]
     MethodNotAllowed { allowed: Vec<SupportedMethod> },
     #[error("408 Request Timeout")]
     RequestTimeout,
+    #[error("409 Conflict")]
+    Conflict {
+        /// What the request conflicts with, like the existing resource.
+        detail: Option<String>,
+    },
+    #[error("410 Gone")]
+    Gone,
     #[error("411 Length Required")]
     LengthRequired,
     #[error("413 Payload Too Lager")]
     PayloadTooLarge,
+    #[error("414 URI Too Long")]
+    UriTooLong,
     #[error("415 Unsupported Media Type")]
     UnsupportedMediaType,
     #[error("Failed to decode request body as UTF-8")]
@@ -45,15 +122,142 @@ pub enum BaseError {
     InvalidParameter {
         query: Vec<InvalidParameter>,
         header: Vec<InvalidParameter>,
+        #[serde(default)]
+        body: Vec<InvalidParameter>,
     },
     #[error("Other error - {0}")]
     Other(#[from] DynError),
+    /// Serialized as `"Unauthorized"`, since the challenge is sent as the header.
+    // Untagged variants must come last.
+    #[error("401 Unauthorized")]
+    #[serde(
+        untagged,
+        serialize_with = "serialize_unauthorized",
+        deserialize_with = "deserialize_unauthorized"
+    )]
+    Unauthorized {
+        /// Challenge sent as the `WWW-Authenticate` header, like `Bearer realm="api"`.
+        www_authenticate: Option<String>,
+    },
+}
+
+fn serialize_unauthorized<S: Serializer>(
+    _www_authenticate: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("Unauthorized")
+}
+
+fn deserialize_unauthorized<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    enum Tag {
+        Unauthorized,
+    }
+
+    Tag::deserialize(deserializer).map(|Tag::Unauthorized| None)
+}
+
+/// Lowercased language tag of the `Accept-Language` header, like the `en-us`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+    pub fn new(tag: &str) -> Self {
+        LanguageTag(tag.trim().to_ascii_lowercase())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Primary language subtag, like the `en` of the `en-us`.
+    pub fn primary(&self) -> &str {
+        self.0.split('-').next().unwrap_or_default()
+    }
+
+    /// Tags of the `Accept-Language` header, the most preferred first.
+    ///
+    /// The wildcard and the tags with `q=0` are skipped.
+    pub fn parse_accept_language(header: &str) -> Vec<LanguageTag> {
+        let mut tags: Vec<(f32, LanguageTag)> = header
+            .split(',')
+            .filter_map(|item| {
+                let mut params = item.split(';');
+                let tag = params.next()?.trim();
+                let q = params
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+
+                if tag.is_empty() || tag == "*" || q <= 0.0 {
+                    None
+                } else {
+                    Some((q, LanguageTag::new(tag)))
+                }
+            })
+            .collect();
+
+        tags.sort_by(|(left, _), (right, _)| right.total_cmp(left));
+        tags.into_iter().map(|(_, tag)| tag).collect()
+    }
+}
+
+#[test]
+fn parse_accept_language() {
+    let tags = LanguageTag::parse_accept_language("fr;q=0.5, ko-KR, *;q=0.1, de;q=0, en;q=0.8");
+    let tags: Vec<_> = tags.iter().map(LanguageTag::as_str).collect();
+    assert_eq!(tags, ["ko-kr", "en", "fr"]);
+    assert_eq!(LanguageTag::new("ko-KR").primary(), "ko");
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct InvalidParameter {
     pub name: Cow<'static, str>,
+    /// Offending input, if known.
     pub value: Option<String>,
+    /// Why the input is rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[test]
+fn parse_example_invalid_parameter() {
+    crate::schema::parse_example::<InvalidParameter>()
+}
+
+impl FtlSchema for InvalidParameter {
+    fn schema() -> oa::Schema {
+        Schema {
+            schema_data: oa::SchemaData {
+                example: Some(json!({"name": "page", "value": "first"})),
+                title: Some("InvalidParameter".into()),
+                description: Some("Parameter which failed to be parsed".into()),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+                properties: {
+                    let mut map = IndexMap::new();
+                    map.insert(
+                        "name".into(),
+                        oa::ReferenceOr::Item(Box::new(String::schema())),
+                    );
+                    map.insert(
+                        "value".into(),
+                        oa::ReferenceOr::Item(Box::new(<Option<String>>::schema())),
+                    );
+                    map.insert(
+                        "reason".into(),
+                        oa::ReferenceOr::Item(Box::new(String::schema())),
+                    );
+                    map
+                },
+                required: vec!["name".into()],
+                ..Default::default()
+            })),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -69,20 +273,235 @@ struct DynErrorSerde {
     error: Option<String>,
 }
 
+impl BaseError {
+    /// Schemas of each variants, paired with its status code if it's fixed.
+    fn variant_schemas() -> Vec<(Option<StatusCode>, Schema)> {
+        let params = || <Vec<InvalidParameter>>::schema();
+
+        vec![
+            (Some(StatusCode::UNAUTHORIZED), unit_variant("Unauthorized")),
+            (Some(StatusCode::FORBIDDEN), unit_variant("Forbidden")),
+            (Some(StatusCode::NOT_FOUND), unit_variant("NotFound")),
+            (
+                Some(StatusCode::METHOD_NOT_ALLOWED),
+                struct_variant(
+                    "MethodNotAllowed",
+                    vec![("allowed", <Vec<SupportedMethod>>::schema())],
+                ),
+            ),
+            (
+                Some(StatusCode::REQUEST_TIMEOUT),
+                unit_variant("RequestTimeout"),
+            ),
+            (
+                Some(StatusCode::CONFLICT),
+                struct_variant("Conflict", vec![("detail", <Option<String>>::schema())]),
+            ),
+            (Some(StatusCode::GONE), unit_variant("Gone")),
+            (
+                Some(StatusCode::LENGTH_REQUIRED),
+                unit_variant("LengthRequired"),
+            ),
+            (
+                Some(StatusCode::PAYLOAD_TOO_LARGE),
+                unit_variant("PayloadTooLarge"),
+            ),
+            (Some(StatusCode::URI_TOO_LONG), unit_variant("UriTooLong")),
+            (
+                Some(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+                unit_variant("UnsupportedMediaType"),
+            ),
+            (Some(StatusCode::BAD_REQUEST), unit_variant("BodyNotUtf8")),
+            (
+                Some(StatusCode::BAD_REQUEST),
+                struct_variant(
+                    "InvalidParameter",
+                    vec![
+                        ("query", params()),
+                        ("header", params()),
+                        ("body", params()),
+                    ],
+                ),
+            ),
+            (None, newtype_variant("Other", DynError::schema())),
+        ]
+    }
+}
+
+/// Schema of the externally tagged unit variant, which is serialized as its name.
+fn unit_variant(name: &str) -> Schema {
+    Schema {
+        schema_data: oa::SchemaData {
+            title: Some(name.into()),
+            example: Some(json!(name)),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+            enumeration: vec![name.into()],
+            ..Default::default()
+        })),
+    }
+}
+
+/// Schema of the externally tagged struct variant, `{ "Name": { ...fields } }`.
+fn struct_variant(name: &str, fields: Vec<(&str, Schema)>) -> Schema {
+    let inner = Schema {
+        schema_data: Default::default(),
+        schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+            required: fields.iter().map(|(field, _)| field.to_string()).collect(),
+            properties: fields
+                .into_iter()
+                .map(|(field, schema)| (field.into(), oa::ReferenceOr::Item(Box::new(schema))))
+                .collect(),
+            ..Default::default()
+        })),
+    };
+
+    newtype_variant(name, inner)
+}
+
+/// Schema of the externally tagged newtype variant, `{ "Name": value }`.
+fn newtype_variant(name: &str, inner: Schema) -> Schema {
+    let mut properties = IndexMap::new();
+    properties.insert(name.into(), oa::ReferenceOr::Item(Box::new(inner)));
+
+    Schema {
+        schema_data: oa::SchemaData {
+            title: Some(name.into()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+            properties,
+            required: vec![name.into()],
+            ..Default::default()
+        })),
+    }
+}
+
+fn one_of(mut schemas: Vec<Schema>) -> Schema {
+    if schemas.len() == 1 {
+        return schemas.pop().unwrap();
+    }
+
+    Schema {
+        schema_data: Default::default(),
+        schema_kind: oa::SchemaKind::OneOf {
+            one_of: schemas.into_iter().map(oa::ReferenceOr::Item).collect(),
+        },
+    }
+}
+
 impl FtlSchema for BaseError {
     fn schema() -> openapiv3::Schema {
-        todo!()
+        let mut schema = one_of(
+            Self::variant_schemas()
+                .into_iter()
+                .map(|(_, schema)| schema)
+                .collect(),
+        );
+        schema.schema_data.title = Some("BaseError".into());
+        schema.schema_data.example = Some(json!("NotFound"));
+        schema
     }
 }
 
+#[test]
+fn parse_example_base_error() {
+    crate::schema::parse_example::<BaseError>()
+}
+
+#[test]
+fn base_error_schema_by_status() {
+    let schema = BaseError::error_schema();
+
+    assert!(schema.default_schema.is_some());
+    assert_eq!(
+        schema.schemas[&StatusCode::URI_TOO_LONG],
+        unit_variant("UriTooLong")
+    );
+    assert!(matches!(
+        schema.schemas[&StatusCode::BAD_REQUEST].schema_kind,
+        oa::SchemaKind::OneOf { .. }
+    ));
+    assert_eq!(BaseError::UriTooLong.status(), StatusCode::URI_TOO_LONG);
+    assert_eq!(BaseError::UriTooLong.to_string(), "414 URI Too Long");
+}
+
+#[test]
+fn auth_errors_status_and_headers() {
+    let forbidden = error_response(&BaseError::Forbidden);
+    assert_eq!(forbidden.status(), StatusCode::FORBIDDEN);
+    assert_eq!(forbidden.body(), r#""Forbidden""#);
+
+    let unauthorized = error_response(&BaseError::Unauthorized {
+        www_authenticate: None,
+    });
+    assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(unauthorized.body(), r#""Unauthorized""#);
+    assert!(unauthorized.headers().get(WWW_AUTHENTICATE).is_none());
+
+    let challenge = error_response(&BaseError::Unauthorized {
+        www_authenticate: Some(r#"Bearer realm="api""#.into()),
+    });
+    assert_eq!(challenge.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(challenge.body(), r#""Unauthorized""#);
+    assert!(matches!(
+        serde_json::from_str(challenge.body()).unwrap(),
+        BaseError::Unauthorized {
+            www_authenticate: None
+        }
+    ));
+    assert_eq!(
+        challenge.headers()[WWW_AUTHENTICATE],
+        r#"Bearer realm="api""#
+    );
+
+    let schema = BaseError::error_schema();
+    assert!(schema.schemas.contains_key(&StatusCode::UNAUTHORIZED));
+    assert_eq!(
+        schema.schemas[&StatusCode::FORBIDDEN],
+        unit_variant("Forbidden")
+    );
+}
+
+#[test]
+fn resource_errors_status_and_body() {
+    let conflict = error_response(&BaseError::Conflict {
+        detail: Some("user already exists".into()),
+    });
+    assert_eq!(conflict.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        conflict.body(),
+        r#"{"Conflict":{"detail":"user already exists"}}"#
+    );
+    assert_eq!(
+        BaseError::Conflict { detail: None }.to_string(),
+        "409 Conflict"
+    );
+
+    let gone = error_response(&BaseError::Gone);
+    assert_eq!(gone.status(), StatusCode::GONE);
+    assert_eq!(gone.body(), r#""Gone""#);
+    assert_eq!(BaseError::Gone.to_string(), "410 Gone");
+
+    let schema = BaseError::error_schema();
+    assert!(schema.schemas.contains_key(&StatusCode::CONFLICT));
+    assert_eq!(schema.schemas[&StatusCode::GONE], unit_variant("Gone"));
+}
+
 impl Error for BaseError {
     fn status(&self) -> StatusCode {
         match self {
+            Self::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
             Self::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            Self::Conflict { .. } => StatusCode::CONFLICT,
+            Self::Gone => StatusCode::GONE,
             Self::LengthRequired => StatusCode::LENGTH_REQUIRED,
             Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UriTooLong => StatusCode::URI_TOO_LONG,
             Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             Self::BodyNotUtf8 => StatusCode::BAD_REQUEST,
             Self::InvalidParameter { .. } => StatusCode::BAD_REQUEST,
@@ -90,8 +509,39 @@ impl Error for BaseError {
         }
     }
 
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Self::Unauthorized {
+            www_authenticate: Some(challenge),
+        } = self
+        {
+            if let Ok(challenge) = HeaderValue::from_str(challenge) {
+                headers.insert(WWW_AUTHENTICATE, challenge);
+            }
+        }
+
+        headers
+    }
+
     fn error_schema() -> ErrorSchema {
-        todo!()
+        let mut default_schema = None;
+        let mut by_status: HashMap<StatusCode, Vec<Schema>> = HashMap::new();
+
+        for (status, schema) in Self::variant_schemas() {
+            match status {
+                Some(status) => by_status.entry(status).or_default().push(schema),
+                None => default_schema = Some(schema),
+            }
+        }
+
+        ErrorSchema {
+            default_schema,
+            schemas: by_status
+                .into_iter()
+                .map(|(status, schemas)| (status, one_of(schemas)))
+                .collect(),
+        }
     }
 }
 
@@ -125,6 +575,63 @@ impl<'de> Deserialize<'de> for DynError {
     }
 }
 
+impl DynError {
+    pub fn new(status: StatusCode, error: impl Into<BoxError>) -> Self {
+        DynError {
+            status,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Implements conversions from the library error into the `DynError` and the `BaseError`
+/// with the given status, so the handlers can use `?` on them.
+macro_rules! dyn_error_from {
+    ($($error:ty => $status:ident,)*) => {$(
+        impl From<$error> for DynError {
+            fn from(error: $error) -> Self {
+                DynError::new(StatusCode::$status, error)
+            }
+        }
+
+        impl From<$error> for BaseError {
+            fn from(error: $error) -> Self {
+                BaseError::Other(error.into())
+            }
+        }
+    )*};
+}
+
+dyn_error_from! {
+    serde_json::Error => BAD_REQUEST,
+    std::io::Error => INTERNAL_SERVER_ERROR,
+    std::str::Utf8Error => BAD_REQUEST,
+}
+
+#[test]
+fn library_error_conversions() {
+    fn convert<E: Into<BaseError>>(error: E) -> BaseError {
+        error.into()
+    }
+
+    let json = serde_json::from_str::<u32>("foo").unwrap_err();
+    let message = json.to_string();
+    let err = convert(json);
+    assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    assert!(err.to_string().contains(&message));
+
+    let io = std::io::Error::other("disk on fire");
+    let err = convert(io);
+    assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(err.to_string().contains("disk on fire"));
+
+    #[allow(invalid_from_utf8)]
+    let utf8 = std::str::from_utf8(b"\xff").unwrap_err();
+    let err = DynError::from(utf8);
+    assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    assert_eq!(err.error.unwrap().to_string(), utf8.to_string());
+}
+
 impl fmt::Display for DynError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.status)?;
@@ -152,6 +659,47 @@ impl Error for String {
     }
 }
 
+#[test]
+fn merge_error_schemas() {
+    let base = BaseError::error_schema();
+    let other = ErrorSchema {
+        default_schema: Some(DynError::schema()),
+        schemas: vec![
+            (StatusCode::BAD_REQUEST, String::schema()),
+            (StatusCode::INTERNAL_SERVER_ERROR, String::schema()),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    let merged = base.clone().merge(other);
+
+    assert_eq!(
+        merged.schemas[&StatusCode::NOT_FOUND],
+        base.schemas[&StatusCode::NOT_FOUND]
+    );
+    assert_eq!(
+        merged.schemas[&StatusCode::INTERNAL_SERVER_ERROR],
+        String::schema()
+    );
+    match &merged.schemas[&StatusCode::BAD_REQUEST].schema_kind {
+        oa::SchemaKind::OneOf { one_of } => {
+            assert_eq!(one_of.len(), 3);
+            assert_eq!(one_of[2], oa::ReferenceOr::Item(String::schema()));
+        }
+        other => panic!("expected oneOf, got {:?}", other),
+    }
+    match merged.default_schema.unwrap().schema_kind {
+        oa::SchemaKind::OneOf { one_of } => assert_eq!(one_of.len(), 2),
+        other => panic!("expected oneOf, got {:?}", other),
+    }
+
+    let twice = String::error_schema().merge(String::error_schema());
+    assert_eq!(
+        twice.schemas[&StatusCode::INTERNAL_SERVER_ERROR],
+        String::schema()
+    );
+}
+
 #[test]
 fn parse_example_dyn_error() {
     crate::schema::parse_example::<DynError>()
@@ -3,15 +3,28 @@
 pub use http::{header, Request, Response, StatusCode};
 pub use hyper::server::Server;
 
+// Lets the derived impls refer to `::ftl` within this crate.
+extern crate self as ftl;
+
 pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
+pub mod codec;
 pub mod error;
+pub mod middleware;
 pub mod router;
 pub mod schema;
 pub mod service;
+pub mod sse;
+pub mod types;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 mod method;
+#[cfg(test)]
+mod test_util;
 
 pub use error::{BaseError, Error};
+pub use ftl_macro::Schema;
+pub use method::SupportedMethod;
 pub use router::Router;
 pub use schema::Schema;
@@ -1,7 +1,12 @@
 use std::convert::TryFrom;
 
 use http::Method;
+use openapiv3 as oa;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use strum::IntoEnumIterator;
+
+use crate::schema::Schema;
 
 #[derive(
     Debug,
@@ -49,6 +54,28 @@ fn supported_method_strum_impl() {
     }
 }
 
+#[test]
+fn parse_example_supported_method() {
+    crate::schema::parse_example::<SupportedMethod>()
+}
+
+impl Schema for SupportedMethod {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("SupportedMethod".into()),
+                description: Some("HTTP method supported by this server".into()),
+                example: Some(json!("Get")),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                enumeration: Self::iter().map(|method| format!("{:?}", method)).collect(),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
 #[derive(Debug, Clone, thiserror::Error)]
 #[error("HTTP method {0} is not supported by this server")]
 pub struct UnsupportedMethod(pub Method);
@@ -70,14 +97,26 @@ impl SupportedMethod {
     }
 
     pub fn request_has_body(self) -> bool {
-        matches!(self, Self::Get | Self::Delete | Self::Head | Self::Options)
+        !matches!(self, Self::Get | Self::Delete | Self::Head | Self::Options)
     }
 
     pub fn response_has_body(self) -> bool {
-        matches!(self, Self::Head)
+        !matches!(self, Self::Head)
     }
 }
 
+#[test]
+fn method_has_body() {
+    assert!(SupportedMethod::Post.request_has_body());
+    assert!(SupportedMethod::Put.request_has_body());
+    assert!(SupportedMethod::Patch.request_has_body());
+    assert!(!SupportedMethod::Get.request_has_body());
+    assert!(!SupportedMethod::Head.request_has_body());
+
+    assert!(SupportedMethod::Get.response_has_body());
+    assert!(!SupportedMethod::Head.response_has_body());
+}
+
 impl TryFrom<Method> for SupportedMethod {
     type Error = UnsupportedMethod;
 
@@ -0,0 +1,94 @@
+//! Reusable middlewares to be applied with [`Router::with`](crate::router::Router::with).
+//!
+//! Each middleware takes the inner handler and returns a new handler
+//! with the same signature, so they can be stacked freely.
+//! To stack them from a list, box them as the [`Middleware`] and pass them
+//! to the [`Router::fold_middleware`](crate::router::Router::fold_middleware).
+
+pub mod auth;
+pub mod body_log;
+pub mod catch_panic;
+pub mod etag;
+pub mod idempotency;
+#[cfg(feature = "tokio-runtime")]
+pub mod timeout;
+
+pub use auth::Auth;
+pub use body_log::BodyLog;
+pub use catch_panic::CatchPanic;
+pub use etag::ETag;
+pub use idempotency::Idempotency;
+#[cfg(feature = "tokio-runtime")]
+pub use timeout::Timeout;
+
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use http::{Request, Response};
+
+use crate::error::BaseError;
+use crate::BoxError;
+
+/// Type erased handler, to stack the middlewares of different types.
+pub type BoxHandler<T> = Arc<
+    dyn for<'a> Fn(
+            Arc<T>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Send
+        + Sync,
+>;
+
+/// Middleware as the trait object, applied with the [`Router::fold_middleware`],
+/// or one by one with the [`Router::layer`] like the `tower::Layer`.
+///
+/// Implemented by the middlewares of this module, and by the closures
+/// which take and return the [`BoxHandler`].
+///
+/// [`Router::fold_middleware`]: crate::router::Router::fold_middleware
+/// [`Router::layer`]: crate::router::Router::layer
+pub trait Middleware<T: ?Sized>: Send + Sync {
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T>;
+}
+
+impl<T: ?Sized, F> Middleware<T> for F
+where
+    F: Fn(BoxHandler<T>) -> BoxHandler<T> + Send + Sync,
+{
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        self(handler)
+    }
+}
+
+/// Erases the type of the handler.
+pub fn boxed<T, H>(handler: H) -> BoxHandler<T>
+where
+    T: Send + Sync + 'static + ?Sized,
+    H: for<'a> Fn(
+            Arc<T>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Send
+        + Sync
+        + 'static,
+{
+    Arc::new(handler)
+}
+
+/// Turns the boxed handler back into the one the `wrap` of the middlewares accepts.
+#[allow(clippy::type_complexity)]
+pub fn unboxed<T>(
+    handler: BoxHandler<T>,
+) -> impl for<'a> Fn(
+    Arc<T>,
+    Request<Result<&'a str, Box<BaseError>>>,
+) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+       + Clone
+       + Send
+       + Sync
+       + 'static
+where
+    T: Send + Sync + 'static + ?Sized,
+{
+    move |app, req| handler(app, req)
+}
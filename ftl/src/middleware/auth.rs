@@ -0,0 +1,150 @@
+//! Authentication guard which runs before the handler.
+
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use http::{Request, Response};
+
+use crate::error::{error_response, BaseError};
+use crate::BoxError;
+
+use super::{boxed, unboxed, BoxHandler, Middleware};
+
+/// Middleware which authenticates the request with the `check` function
+/// before the handler runs.
+///
+/// On success the returned claims are inserted into the request extensions,
+/// where the handler can take them with the [`ext`](crate::router::ext).
+/// On failure the handler is skipped and the error is returned as the response,
+/// usually the [`BaseError::Unauthorized`] or the [`BaseError::Forbidden`].
+///
+/// ```ignore
+/// let router = router.with(|handler| {
+///     Auth::new(|req: &Request<_>| match req.headers().get(AUTHORIZATION) {
+///         Some(token) if token == "Bearer secret" => Ok(Claims { admin: true }),
+///         _ => Err(BaseError::Unauthorized { www_authenticate: None }),
+///     })
+///     .wrap(handler)
+/// });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Auth<F> {
+    check: F,
+}
+
+impl<F> Auth<F> {
+    pub fn new(check: F) -> Self {
+        Self { check }
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn wrap<T, H, C>(
+        self,
+        handler: H,
+    ) -> impl for<'a> Fn(
+        Arc<T>,
+        Request<Result<&'a str, Box<BaseError>>>,
+    ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+           + Clone
+           + Send
+           + Sync
+           + 'static
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        F: for<'a> Fn(&Request<Result<&'a str, Box<BaseError>>>) -> Result<C, BaseError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        C: Clone + Send + Sync + 'static,
+    {
+        let check = self.check;
+
+        move |app, mut req| match check(&req) {
+            Ok(claims) => {
+                req.extensions_mut().insert(claims);
+                handler(app, req)
+            }
+            Err(err) => Box::pin(async move { Ok(error_response(&err)) }),
+        }
+    }
+}
+
+impl<T, F, C> Middleware<T> for Auth<F>
+where
+    T: Send + Sync + 'static + ?Sized,
+    F: for<'a> Fn(&Request<Result<&'a str, Box<BaseError>>>) -> Result<C, BaseError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    C: Clone + Send + Sync + 'static,
+{
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        boxed(self.clone().wrap(unboxed(handler)))
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+struct Claims {
+    user: &'static str,
+}
+
+#[cfg(test)]
+fn whoami(
+    _app: Arc<()>,
+    req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    let user = crate::router::ext::<Claims, _>(&req).map(|claims| claims.user);
+
+    Box::pin(async move { Ok(Response::new(user.unwrap_or("nobody").into())) })
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn auth_guards_handler() {
+    use http::header::AUTHORIZATION;
+    use http::StatusCode;
+
+    let router = crate::Router::from_handler(Arc::new(()), whoami).with(|handler| {
+        Auth::new(|req: &Request<Result<&str, Box<BaseError>>>| {
+            match req.headers().get(AUTHORIZATION) {
+                Some(token) if token == "Bearer secret" => Ok(Claims { user: "ferris" }),
+                _ => Err(BaseError::Unauthorized {
+                    www_authenticate: Some("Bearer".into()),
+                }),
+            }
+        })
+        .wrap(handler)
+    });
+
+    let req = Request::get("/")
+        .header(AUTHORIZATION, "Bearer secret")
+        .body(Ok(""))
+        .unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "ferris");
+
+    let req = Request::get("/").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers()[http::header::WWW_AUTHENTICATE], "Bearer");
+
+    let req = Request::get("/")
+        .header(AUTHORIZATION, "Bearer guess")
+        .body(Ok(""))
+        .unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.body(), r#""Unauthorized""#);
+}
@@ -0,0 +1,154 @@
+//! Request body logging with the secret fields redacted.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use http::{Request, Response};
+use serde_json::Value;
+use tracing::Level;
+
+use crate::error::BaseError;
+use crate::BoxError;
+
+use super::{boxed, unboxed, BoxHandler, Middleware};
+
+/// Middleware which logs the request body via the `tracing`,
+/// with the values of the listed JSON fields masked as `"***"`.
+///
+/// Bodies which aren't JSON are logged as `<non-json body>`.
+///
+/// ```ignore
+/// let router = router.with(|handler| BodyLog::new().redact("secret").wrap(handler));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BodyLog {
+    level: Level,
+    redact: Vec<Cow<'static, str>>,
+}
+
+impl BodyLog {
+    /// Logs at the `DEBUG` level, redacting the `password` and the `token` fields.
+    pub fn new() -> Self {
+        Self {
+            level: Level::DEBUG,
+            redact: vec!["password".into(), "token".into()],
+        }
+    }
+
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Adds the field name to redact, at any depth of the body.
+    pub fn redact(mut self, field: impl Into<Cow<'static, str>>) -> Self {
+        self.redact.push(field.into());
+        self
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn wrap<T, H>(
+        self,
+        handler: H,
+    ) -> impl for<'a> Fn(
+        Arc<T>,
+        Request<Result<&'a str, Box<BaseError>>>,
+    ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+           + Clone
+           + Send
+           + Sync
+           + 'static
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let config = Arc::new(self);
+
+        move |app, req| {
+            if let Ok(body) = req.body() {
+                let body = config.redacted(body);
+                let (method, path) = (req.method(), req.uri().path());
+
+                match config.level {
+                    Level::ERROR => tracing::error!(%method, path, body = %body, "request body"),
+                    Level::WARN => tracing::warn!(%method, path, body = %body, "request body"),
+                    Level::INFO => tracing::info!(%method, path, body = %body, "request body"),
+                    Level::DEBUG => tracing::debug!(%method, path, body = %body, "request body"),
+                    Level::TRACE => tracing::trace!(%method, path, body = %body, "request body"),
+                }
+            }
+
+            handler(app, req)
+        }
+    }
+
+    fn redacted(&self, body: &str) -> String {
+        match serde_json::from_str::<Value>(body) {
+            Ok(mut value) => {
+                self.redact_value(&mut value);
+                value.to_string()
+            }
+            Err(_) => "<non-json body>".into(),
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.redact.iter().any(|field| field == key) {
+                        *value = Value::from("***");
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static + ?Sized> Middleware<T> for BodyLog {
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        boxed(self.clone().wrap(unboxed(handler)))
+    }
+}
+
+impl Default for BodyLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn body_log_redacts_secrets() {
+    use crate::test_util::Captured;
+
+    let captured = Captured::default();
+    let subscriber = captured.subscriber(Level::INFO);
+
+    let router = crate::Router::from_handler(Arc::new(()), crate::router::echo_path)
+        .with(|handler| BodyLog::new().level(Level::INFO).wrap(handler));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let body = r#"{"user":"ferris","password":"hunter2","nested":[{"token":"abc"}]}"#;
+        drop(router.call(Request::post("/login").body(Ok(body)).unwrap()));
+        drop(router.call(Request::post("/raw").body(Ok("not json")).unwrap()));
+    });
+
+    let log = captured.log();
+    assert!(log.contains(r#""user":"ferris""#), "{}", log);
+    assert!(log.contains(r#""password":"***""#), "{}", log);
+    assert!(log.contains(r#""token":"***""#), "{}", log);
+    assert!(!log.contains("hunter2") && !log.contains("abc"), "{}", log);
+    assert!(log.contains("<non-json body>"), "{}", log);
+}
@@ -0,0 +1,132 @@
+//! Conversion of the handler panics into the error responses.
+
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use futures_util::future::{BoxFuture, FutureExt};
+use http::{Request, Response, StatusCode};
+
+use crate::error::{error_response, BaseError, DynError};
+use crate::BoxError;
+
+use super::{boxed, unboxed, BoxHandler, Middleware};
+
+/// Middleware which responds `500 Internal Server Error` when the handler panics,
+/// instead of dropping the connection.
+///
+/// The [`DynError`] of the response carries the panic message in the debug builds only.
+///
+/// ```ignore
+/// let router = router.with(|handler| CatchPanic::new().wrap(handler));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CatchPanic;
+
+impl CatchPanic {
+    pub fn new() -> Self {
+        CatchPanic
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn wrap<T, H>(
+        self,
+        handler: H,
+    ) -> impl for<'a> Fn(
+        Arc<T>,
+        Request<Result<&'a str, Box<BaseError>>>,
+    ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+           + Clone
+           + Send
+           + Sync
+           + 'static
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        move |app, req| {
+            let resp = match std::panic::catch_unwind(AssertUnwindSafe(|| handler(app, req))) {
+                Ok(resp) => resp,
+                Err(panic) => return Box::pin(async move { Ok(panic_response(panic)) }),
+            };
+
+            Box::pin(async move {
+                match AssertUnwindSafe(resp).catch_unwind().await {
+                    Ok(resp) => resp,
+                    Err(panic) => Ok(panic_response(panic)),
+                }
+            })
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static + ?Sized> Middleware<T> for CatchPanic {
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        boxed(self.wrap(unboxed(handler)))
+    }
+}
+
+fn panic_response(panic: Box<dyn Any + Send>) -> Response<String> {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|msg| msg.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "handler panicked".into());
+    tracing::error!("handler panicked: {}", message);
+
+    let error = if cfg!(debug_assertions) {
+        DynError::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    } else {
+        DynError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: None,
+        }
+    };
+
+    error_response(&error)
+}
+
+#[cfg(test)]
+fn panicking(
+    _app: Arc<()>,
+    req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    if req.uri().path() == "/sync" {
+        panic!("sync panic");
+    }
+
+    Box::pin(async move {
+        if req.uri().path() == "/async" {
+            panic!("async panic");
+        }
+        Ok(Response::new("fine".into()))
+    })
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn catch_panic_responds_500() {
+    let router = crate::Router::from_handler(Arc::new(()), panicking)
+        .with(|handler| CatchPanic::new().wrap(handler));
+
+    for path in &["/sync", "/async"] {
+        let req = Request::get(*path).body(Ok("")).unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        if cfg!(debug_assertions) {
+            assert!(resp.body().contains("panic"));
+        }
+    }
+
+    let req = Request::get("/").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "fine");
+}
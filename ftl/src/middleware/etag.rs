@@ -0,0 +1,164 @@
+//! Conditional GET responses with the weak `ETag` validator.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use http::header::{HeaderValue, ETAG, IF_NONE_MATCH};
+use http::{Method, Request, Response, StatusCode};
+
+use crate::error::BaseError;
+use crate::BoxError;
+
+use super::{boxed, unboxed, BoxHandler, Middleware};
+
+/// Middleware which tags successful `GET` responses with a weak `ETag`
+/// computed from the response body, and replies `304 Not Modified`
+/// when the request's `If-None-Match` already matches it.
+///
+/// ```ignore
+/// let router = router.with(|handler| ETag::new().wrap(handler));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ETag {
+    hasher: fn(&[u8]) -> u64,
+}
+
+impl ETag {
+    pub fn new() -> Self {
+        Self {
+            hasher: default_hasher,
+        }
+    }
+
+    /// Replaces the hash function used to compute the tag from the body.
+    pub fn hasher(mut self, hasher: fn(&[u8]) -> u64) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn wrap<T, H>(
+        self,
+        handler: H,
+    ) -> impl for<'a> Fn(
+        Arc<T>,
+        Request<Result<&'a str, Box<BaseError>>>,
+    ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+           + Clone
+           + Send
+           + Sync
+           + 'static
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let hasher = self.hasher;
+
+        move |app, req| {
+            let is_get = req.method() == Method::GET;
+            let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+            let resp = handler(app, req);
+
+            Box::pin(async move {
+                let mut resp = resp.await?;
+
+                if !is_get || !resp.status().is_success() {
+                    return Ok(resp);
+                }
+
+                let tag = format!("W/\"{:016x}\"", hasher(resp.body().as_bytes()));
+                let matched = if_none_match
+                    .as_ref()
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| matches(value, &tag));
+                let tag = HeaderValue::from_str(&tag)?;
+
+                if matched {
+                    let mut not_modified = Response::new(String::new());
+                    *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+                    not_modified.headers_mut().insert(ETAG, tag);
+                    return Ok(not_modified);
+                }
+
+                resp.headers_mut().insert(ETAG, tag);
+                Ok(resp)
+            })
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static + ?Sized> Middleware<T> for ETag {
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        boxed(self.wrap(unboxed(handler)))
+    }
+}
+
+impl Default for ETag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_hasher(body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(body);
+    hasher.finish()
+}
+
+/// Weak comparison of the `If-None-Match` list against the tag.
+fn matches(if_none_match: &str, tag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    let tag = opaque(tag);
+
+    if_none_match
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == tag)
+}
+
+#[cfg(test)]
+fn hello(
+    _app: Arc<()>,
+    _req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    Box::pin(async { Ok(Response::new("hello".into())) })
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn etag_miss_returns_tag() {
+    let router =
+        crate::Router::from_handler(Arc::new(()), hello).with(|handler| ETag::new().wrap(handler));
+
+    let req = Request::get("/").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "hello");
+    assert!(resp.headers()[ETAG].to_str().unwrap().starts_with("W/\""));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn etag_hit_returns_not_modified() {
+    let router = crate::Router::from_handler(Arc::new(()), hello)
+        .with(|handler| ETag::new().hasher(|_| 42).wrap(handler));
+
+    let req = Request::get("/")
+        .header(IF_NONE_MATCH, "\"foo\", W/\"000000000000002a\"")
+        .body(Ok(""))
+        .unwrap();
+    let resp = router.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.body(), "");
+    assert_eq!(resp.headers()[ETAG], "W/\"000000000000002a\"");
+}
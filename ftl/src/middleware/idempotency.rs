@@ -0,0 +1,595 @@
+//! Replay of the responses for the retried requests with the same `Idempotency-Key`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use http::header::{HeaderName, HeaderValue};
+use http::{HeaderMap, Request, Response, StatusCode};
+
+use crate::error::{error_response, BaseError, DynError};
+use crate::BoxError;
+
+use super::{boxed, unboxed, BoxHandler, Middleware};
+
+/// Header which carries the key chosen by the client.
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Header set on the replayed responses.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// Response stored for the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    /// Hash of the request body which made the response, to reject the reuse
+    /// of the key for another body.
+    pub request_hash: u64,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Storage of the responses by the idempotency key.
+///
+/// The keys are scoped by the method, the path and the caller, if any.
+/// The store decides how long the responses are kept,
+/// like the `EX` of the Redis `SET`.
+pub trait IdempotencyStore: Send + Sync + 'static {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<StoredResponse>>;
+
+    fn put(&self, key: String, response: StoredResponse) -> BoxFuture<'_, ()>;
+}
+
+/// In-memory store which keeps up to `capacity` responses for the `ttl`,
+/// evicting the least recently used one when it's full.
+#[derive(Debug)]
+pub struct MemoryStore {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    /// Keys from the least recently used one. Each use pushes the key again,
+    /// so the ones whose `tick` doesn't match the entry are stale and skipped.
+    order: VecDeque<(String, u64)>,
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    tick: u64,
+    stored_at: Instant,
+    response: StoredResponse,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Option<StoredResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.map.get(key)?;
+        if entry.stored_at.elapsed() >= self.ttl {
+            entries.map.remove(key);
+            return None;
+        }
+
+        let response = entry.response.clone();
+        entries.touch(key.to_owned(), self.capacity);
+        Some(response)
+    }
+
+    fn store(&self, key: String, response: StoredResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let entry = Entry {
+            tick: 0,
+            stored_at: Instant::now(),
+            response,
+        };
+        entries.map.insert(key.clone(), entry);
+        entries.touch(key, self.capacity);
+
+        while entries.map.len() > self.capacity {
+            let (key, tick) = match entries.order.pop_front() {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            if entries.is_current(&key, tick) {
+                entries.map.remove(&key);
+            }
+        }
+    }
+}
+
+impl Entries {
+    /// Marks the entry of the `key` as the most recently used.
+    fn touch(&mut self, key: String, capacity: usize) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.map.get_mut(&key) {
+            entry.tick = tick;
+        }
+        self.order.push_back((key, tick));
+
+        // Drops the stale keys once they outnumber the live ones, amortized O(1)
+        if self.order.len() > 2 * capacity {
+            let mut order = std::mem::take(&mut self.order);
+            order.retain(|(key, tick)| self.is_current(key, *tick));
+            self.order = order;
+        }
+    }
+
+    fn is_current(&self, key: &str, tick: u64) -> bool {
+        self.map.get(key).is_some_and(|entry| entry.tick == tick)
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<StoredResponse>> {
+        let response = self.lookup(key);
+        Box::pin(async move { response })
+    }
+
+    fn put(&self, key: String, response: StoredResponse) -> BoxFuture<'_, ()> {
+        self.store(key, response);
+        Box::pin(async {})
+    }
+}
+
+type Caller = dyn Fn(&Request<Result<&str, Box<BaseError>>>) -> Option<String> + Send + Sync;
+
+/// Middleware which replays the first response to the unsafe requests
+/// with the same `Idempotency-Key` header, without running the handler again.
+///
+/// The keys are scoped by the method, the path and the [`caller`](Idempotency::caller).
+/// Requests of the safe methods or without the key pass through.
+/// The `5xx` responses aren't stored so the retries can succeed.
+/// Replayed responses have the `Idempotent-Replayed: true` header.
+///
+/// Reusing the key with another body is rejected with `422 Unprocessable Entity`,
+/// and retrying while the first request is still running with `409 Conflict`.
+///
+/// ```ignore
+/// let router = router.with(|handler| {
+///     Idempotency::new(MemoryStore::new(10_000, Duration::from_secs(24 * 60 * 60)))
+///         .caller(|req| Some(req.headers().get("x-api-key")?.to_str().ok()?.to_owned()))
+///         .wrap(handler)
+/// });
+/// ```
+pub struct Idempotency<S> {
+    store: Arc<S>,
+    caller: Option<Arc<Caller>>,
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Removes the key from the in-flight ones when the request completes or is cancelled.
+struct InFlight {
+    keys: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl<S> Clone for Idempotency<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            caller: self.caller.clone(),
+            in_flight: Arc::clone(&self.in_flight),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Idempotency<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Idempotency")
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: IdempotencyStore> Idempotency<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            caller: None,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Scopes the keys by the caller the `caller` identifies, like the user ID
+    /// or the API key, so the callers can't replay the responses of each other.
+    pub fn caller<F>(mut self, caller: F) -> Self
+    where
+        F: Fn(&Request<Result<&str, Box<BaseError>>>) -> Option<String> + Send + Sync + 'static,
+    {
+        self.caller = Some(Arc::new(caller));
+        self
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn wrap<T, H>(
+        self,
+        handler: H,
+    ) -> impl for<'a> Fn(
+        Arc<T>,
+        Request<Result<&'a str, Box<BaseError>>>,
+    ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+           + Clone
+           + Send
+           + Sync
+           + 'static
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        move |app, req| {
+            let key = match req.headers().get(IDEMPOTENCY_KEY) {
+                Some(key) if !req.method().is_safe() => key.to_str().ok(),
+                _ => None,
+            };
+            let key = match key {
+                Some(key) => self.scoped_key(&req, key),
+                None => return handler(app, req),
+            };
+            let request_hash = fnv1a(req.body().as_ref().map_or("", |body| body));
+
+            let handler = handler.clone();
+            let this = self.clone();
+
+            Box::pin(async move {
+                if let Some(stored) = this.store.get(&key).await {
+                    return Ok(replay(stored, request_hash));
+                }
+
+                let _in_flight = match this.start(&key) {
+                    Some(in_flight) => in_flight,
+                    None => {
+                        return Ok(error_response(&BaseError::Conflict {
+                            detail: Some("request with the idempotency key is in progress".into()),
+                        }))
+                    }
+                };
+                // The first request may have stored the response and finished
+                // while the store was looked up above.
+                if let Some(stored) = this.store.get(&key).await {
+                    return Ok(replay(stored, request_hash));
+                }
+
+                let resp = handler(app, req).await?;
+                if !resp.status().is_server_error() {
+                    let stored = StoredResponse {
+                        request_hash,
+                        status: resp.status(),
+                        headers: resp.headers().clone(),
+                        body: resp.body().clone(),
+                    };
+                    this.store.put(key, stored).await;
+                }
+
+                Ok(resp)
+            })
+        }
+    }
+
+    /// Key within the store, which never collides across the scopes
+    /// since the method and the path can't contain the line breaks.
+    fn scoped_key(&self, req: &Request<Result<&str, Box<BaseError>>>, key: &str) -> String {
+        let caller = self.caller.as_ref().and_then(|caller| caller(req));
+        format!(
+            "{}\n{}\n{}\n{}",
+            req.method(),
+            req.uri().path(),
+            key,
+            caller.unwrap_or_default()
+        )
+    }
+
+    fn start(&self, key: &str) -> Option<InFlight> {
+        let mut keys = self.in_flight.lock().unwrap();
+        if !keys.insert(key.to_owned()) {
+            return None;
+        }
+
+        Some(InFlight {
+            keys: Arc::clone(&self.in_flight),
+            key: key.to_owned(),
+        })
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.keys.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Response replayed from the `stored` one,
+/// or the `422 Unprocessable Entity` if it's stored for another body.
+fn replay(stored: StoredResponse, request_hash: u64) -> Response<String> {
+    if stored.request_hash != request_hash {
+        return error_response(&BaseError::Other(DynError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            error: Some("idempotency key is reused with another body".into()),
+        }));
+    }
+
+    let mut resp = Response::new(stored.body);
+    *resp.status_mut() = stored.status;
+    *resp.headers_mut() = stored.headers;
+    resp.headers_mut()
+        .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    resp
+}
+
+/// FNV-1a hash, which is stable across the processes sharing the store.
+fn fnv1a(body: &str) -> u64 {
+    body.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl<T, S> Middleware<T> for Idempotency<S>
+where
+    T: Send + Sync + 'static + ?Sized,
+    S: IdempotencyStore,
+{
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        boxed(self.clone().wrap(unboxed(handler)))
+    }
+}
+
+#[cfg(test)]
+fn count_calls(
+    app: Arc<std::sync::atomic::AtomicUsize>,
+    _req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    let count = app.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+    Box::pin(async move { Ok(Response::new(count.to_string())) })
+}
+
+#[cfg(test)]
+fn post_with_key(key: &str) -> Request<Result<&'static str, Box<BaseError>>> {
+    Request::post("/")
+        .header(IDEMPOTENCY_KEY, key)
+        .body(Ok(""))
+        .unwrap()
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn idempotency_replays_duplicates() {
+    let router = crate::Router::from_handler(Arc::default(), count_calls).with(|handler| {
+        Idempotency::new(MemoryStore::new(16, Duration::from_secs(60))).wrap(handler)
+    });
+
+    let resp = router.call(post_with_key("a")).await.unwrap();
+    assert_eq!(resp.body(), "1");
+    assert!(!resp.headers().contains_key(IDEMPOTENT_REPLAYED));
+
+    let resp = router.call(post_with_key("a")).await.unwrap();
+    assert_eq!(resp.body(), "1");
+    assert_eq!(resp.headers()[IDEMPOTENT_REPLAYED], "true");
+
+    let resp = router.call(post_with_key("b")).await.unwrap();
+    assert_eq!(resp.body(), "2");
+
+    let req = Request::get("/")
+        .header(IDEMPOTENCY_KEY, "a")
+        .body(Ok(""))
+        .unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.body(), "3");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn idempotency_key_expires() {
+    let router = crate::Router::from_handler(Arc::default(), count_calls).with(|handler| {
+        Idempotency::new(MemoryStore::new(16, Duration::from_millis(50))).wrap(handler)
+    });
+
+    let resp = router.call(post_with_key("a")).await.unwrap();
+    assert_eq!(resp.body(), "1");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let resp = router.call(post_with_key("a")).await.unwrap();
+    assert_eq!(resp.body(), "2");
+    assert!(!resp.headers().contains_key(IDEMPOTENT_REPLAYED));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn idempotency_key_is_scoped() {
+    let router = crate::Router::from_handler(Arc::default(), count_calls).with(|handler| {
+        Idempotency::new(MemoryStore::new(16, Duration::from_secs(60)))
+            .caller(|req| Some(req.headers().get("x-user")?.to_str().ok()?.to_owned()))
+            .wrap(handler)
+    });
+    let request = |method: &str, path: &str, user: &str| {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(IDEMPOTENCY_KEY, "a")
+            .header("x-user", user)
+            .body(Ok(""))
+            .unwrap()
+    };
+
+    let resp = router.call(request("POST", "/", "ferris")).await.unwrap();
+    assert_eq!(resp.body(), "1");
+    let resp = router.call(request("POST", "/", "ferris")).await.unwrap();
+    assert_eq!(resp.body(), "1");
+
+    let resp = router.call(request("PUT", "/", "ferris")).await.unwrap();
+    assert_eq!(resp.body(), "2");
+    let resp = router
+        .call(request("POST", "/other", "ferris"))
+        .await
+        .unwrap();
+    assert_eq!(resp.body(), "3");
+    let resp = router.call(request("POST", "/", "corro")).await.unwrap();
+    assert_eq!(resp.body(), "4");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn idempotency_rejects_another_body() {
+    let router = crate::Router::from_handler(Arc::default(), count_calls).with(|handler| {
+        Idempotency::new(MemoryStore::new(16, Duration::from_secs(60))).wrap(handler)
+    });
+    let request = |body| {
+        Request::post("/")
+            .header(IDEMPOTENCY_KEY, "a")
+            .body(Ok(body))
+            .unwrap()
+    };
+
+    let resp = router.call(request(r#"{"amount":1}"#)).await.unwrap();
+    assert_eq!(resp.body(), "1");
+
+    let resp = router.call(request(r#"{"amount":2}"#)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let resp = router.call(request(r#"{"amount":1}"#)).await.unwrap();
+    assert_eq!(resp.body(), "1");
+    assert_eq!(resp.headers()[IDEMPOTENT_REPLAYED], "true");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn idempotency_conflicts_while_in_flight() {
+    fn slow(
+        app: Arc<std::sync::atomic::AtomicUsize>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            count_calls(app, req).await
+        })
+    }
+
+    let router = crate::Router::from_handler(Arc::default(), slow).with(|handler| {
+        Idempotency::new(MemoryStore::new(16, Duration::from_secs(60))).wrap(handler)
+    });
+
+    let (first, second) = tokio::join!(
+        router.call(post_with_key("a")),
+        router.call(post_with_key("a"))
+    );
+    let mut statuses = [first.unwrap().status(), second.unwrap().status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+
+    let resp = router.call(post_with_key("a")).await.unwrap();
+    assert_eq!(resp.body(), "1");
+    assert_eq!(resp.headers()[IDEMPOTENT_REPLAYED], "true");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn idempotency_rechecks_after_claiming() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Delays the lookup once, like a slow remote store.
+    struct SlowStore {
+        inner: MemoryStore,
+        delay_next: Arc<AtomicBool>,
+    }
+
+    impl IdempotencyStore for SlowStore {
+        fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<StoredResponse>> {
+            let stored = self.inner.lookup(key);
+            let delayed = self.delay_next.swap(false, Ordering::SeqCst);
+            Box::pin(async move {
+                if delayed {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                stored
+            })
+        }
+
+        fn put(&self, key: String, response: StoredResponse) -> BoxFuture<'_, ()> {
+            self.inner.put(key, response)
+        }
+    }
+
+    fn slow(
+        app: Arc<AtomicUsize>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            count_calls(app, req).await
+        })
+    }
+
+    let delay_next = Arc::new(AtomicBool::new(false));
+    let store = SlowStore {
+        inner: MemoryStore::new(16, Duration::from_secs(60)),
+        delay_next: Arc::clone(&delay_next),
+    };
+    let router = crate::Router::from_handler(Arc::default(), slow)
+        .with(|handler| Idempotency::new(store).wrap(handler));
+
+    // The retry looks up the store while the first one is running,
+    // and claims the key after it's done.
+    let (first, second) = tokio::join!(router.call(post_with_key("a")), async {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        delay_next.store(true, Ordering::SeqCst);
+        router.call(post_with_key("a")).await
+    });
+    assert_eq!(first.unwrap().body(), "1");
+    let second = second.unwrap();
+    assert_eq!(second.body(), "1");
+    assert_eq!(second.headers()[IDEMPOTENT_REPLAYED], "true");
+}
+
+#[test]
+fn memory_store_evicts_least_recently_used() {
+    let store = MemoryStore::new(2, Duration::from_secs(60));
+    let stored = |body: &str| StoredResponse {
+        request_hash: 0,
+        status: StatusCode::OK,
+        headers: HeaderMap::new(),
+        body: body.into(),
+    };
+
+    store.store("a".into(), stored("a"));
+    store.store("b".into(), stored("b"));
+    assert_eq!(store.lookup("a"), Some(stored("a")));
+
+    store.store("c".into(), stored("c"));
+    assert_eq!(store.lookup("b"), None);
+    assert_eq!(store.lookup("a"), Some(stored("a")));
+    assert_eq!(store.lookup("c"), Some(stored("c")));
+
+    // Repeated lookups keep the order bounded
+    for _ in 0..100 {
+        store.lookup("a");
+    }
+    assert!(store.entries.lock().unwrap().order.len() <= 4);
+    store.store("d".into(), stored("d"));
+    assert_eq!(store.lookup("c"), None);
+    assert_eq!(store.lookup("a"), Some(stored("a")));
+}
@@ -0,0 +1,160 @@
+//! Handler timeout which tells the overload from the slow handler.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use http::{Request, Response, StatusCode};
+use tokio::sync::Semaphore;
+
+use crate::error::{error_response, BaseError, DynError};
+use crate::BoxError;
+
+use super::{boxed, unboxed, BoxHandler, Middleware};
+
+/// Middleware which responds with the error once the `duration` elapses.
+///
+/// With the [`concurrency_limit`](Timeout::concurrency_limit), requests wait
+/// for the handler to start. If the timeout fires while still waiting, the server
+/// is overloaded and it responds `503 Service Unavailable`. Otherwise the handler
+/// was too slow and it responds `504 Gateway Timeout`.
+///
+/// ```ignore
+/// let router = router.with(|handler| {
+///     Timeout::new(Duration::from_secs(10))
+///         .concurrency_limit(64)
+///         .wrap(handler)
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Timeout {
+    duration: Duration,
+    limit: Option<Arc<Semaphore>>,
+}
+
+impl Timeout {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            limit: None,
+        }
+    }
+
+    /// Limits the number of handlers running at the same time.
+    pub fn concurrency_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn wrap<T, H>(
+        self,
+        handler: H,
+    ) -> impl for<'a> Fn(
+        Arc<T>,
+        Request<Result<&'a str, Box<BaseError>>>,
+    ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+           + Clone
+           + Send
+           + Sync
+           + 'static
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        move |app, req| {
+            let handler = handler.clone();
+            let duration = self.duration;
+            let limit = self.limit.clone();
+
+            Box::pin(async move {
+                let deadline = tokio::time::Instant::now() + duration;
+
+                let _permit = match limit {
+                    Some(limit) => {
+                        match tokio::time::timeout_at(deadline, limit.acquire_owned()).await {
+                            // The permit may be released right at the deadline.
+                            Ok(permit) if tokio::time::Instant::now() < deadline => Some(permit?),
+                            _ => {
+                                return Ok(timeout_response(
+                                    StatusCode::SERVICE_UNAVAILABLE,
+                                    "timed out waiting for the handler to start",
+                                ))
+                            }
+                        }
+                    }
+                    None => None,
+                };
+
+                match tokio::time::timeout_at(deadline, handler(app, req)).await {
+                    Ok(resp) => resp,
+                    Err(_) => Ok(timeout_response(
+                        StatusCode::GATEWAY_TIMEOUT,
+                        "handler timed out",
+                    )),
+                }
+            })
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static + ?Sized> Middleware<T> for Timeout {
+    fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+        boxed(self.clone().wrap(unboxed(handler)))
+    }
+}
+
+fn timeout_response(status: StatusCode, reason: &'static str) -> Response<String> {
+    error_response(&DynError::new(status, reason))
+}
+
+#[cfg(test)]
+fn sleep_for_path(
+    _app: Arc<()>,
+    req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    let millis: u64 = req.uri().path()[1..].parse().unwrap();
+    Box::pin(async move {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        Ok(Response::new("done".into()))
+    })
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn timeout_slow_handler_is_504() {
+    let router = crate::Router::from_handler(Arc::new(()), sleep_for_path)
+        .with(|handler| Timeout::new(Duration::from_millis(50)).wrap(handler));
+
+    let resp = router.call(Request::get("/0").body(Ok("")).unwrap()).await;
+    assert_eq!(resp.unwrap().status(), StatusCode::OK);
+
+    let resp = router
+        .call(Request::get("/1000").body(Ok("")).unwrap())
+        .await;
+    assert_eq!(resp.unwrap().status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn timeout_while_queued_is_503() {
+    let router = crate::Router::from_handler(Arc::new(()), sleep_for_path).with(|handler| {
+        Timeout::new(Duration::from_millis(100))
+            .concurrency_limit(1)
+            .wrap(handler)
+    });
+
+    let busy = router.call(Request::get("/1000").body(Ok("")).unwrap());
+    let queued = router.call(Request::get("/0").body(Ok("")).unwrap());
+    let (busy, queued) = futures_util::future::join(busy, queued).await;
+
+    assert_eq!(busy.unwrap().status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(queued.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+}
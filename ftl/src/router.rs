@@ -4,16 +4,32 @@
 //! It can be generated from the api trait.
 //! You can apply some tests on it, or create another router with some combinators.
 //! At the end the [`Service`](crate::service::Service) can be generated from the router.
+//!
+//! Middlewares pass values to the handlers with the request extensions.
+//! Insert them with the `request.extensions_mut().insert(value)`,
+//! and read them within the handler with the [`ext`].
 
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
 use std::fmt;
+use std::future::Future;
+#[cfg(feature = "tokio-runtime")]
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use futures_util::future::BoxFuture;
-use hyper::{Request, Response, Server};
+use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use http::StatusCode;
+use hyper::{Request, Response};
+use openapiv3 as oa;
 
-use crate::error::BaseError;
-use crate::service::Service;
+use crate::codec::{self, ExpectJson};
+use crate::error::{error_response, BaseError, Error, ErrorSchema};
+use crate::method::SupportedMethod;
+use crate::middleware::{self, Middleware};
+use crate::schema::Schema;
+#[cfg(feature = "tokio-runtime")]
+use crate::service::{Builder, RunError, Service};
 use crate::BoxError;
 
 pub type Handler<T> = for<'a> fn(
@@ -35,6 +51,117 @@ where
 {
     pub app: Arc<T>,
     pub handler: H,
+    pub(crate) routes: Arc<Vec<Route>>,
+}
+
+/// Metadata of the registered route, used to build the OpenAPI document.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub method: SupportedMethod,
+    pub path: Cow<'static, str>,
+    /// Schema of the request body. `None` if the route takes no body.
+    pub request_schema: Option<oa::Schema>,
+    pub response_schema: oa::Schema,
+    pub error_schema: ErrorSchema,
+    pub doc: OperationDoc,
+    pub(crate) response_media_type: &'static str,
+    /// Checks the successful response body against the response type.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    pub(crate) validate_response: fn(&str) -> Result<(), String>,
+}
+
+/// Documentation of the operation, which is empty by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperationDoc {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Route {
+    /// Generates the OpenAPI operation of this route.
+    ///
+    /// Error schemas are listed for each status code,
+    /// and the ones without the status become the default response.
+    pub fn operation(&self) -> oa::Operation {
+        let content = |media_type: &str, schema: &oa::Schema| {
+            Some((
+                media_type.to_owned(),
+                oa::MediaType {
+                    schema: Some(oa::ReferenceOr::Item(schema.clone())),
+                    ..Default::default()
+                },
+            ))
+            .into_iter()
+            .collect()
+        };
+        let response = |description: &str, media_type: &str, schema: &oa::Schema| {
+            oa::ReferenceOr::Item(oa::Response {
+                description: description.to_owned(),
+                content: content(media_type, schema),
+                ..Default::default()
+            })
+        };
+
+        let mut responses = oa::Responses::default();
+        responses.responses.insert(
+            oa::StatusCode::Code(StatusCode::OK.as_u16()),
+            response("OK", self.response_media_type, &self.response_schema),
+        );
+
+        let mut errors: Vec<_> = self.error_schema.schemas.iter().collect();
+        errors.sort_by_key(|(status, _)| *status);
+        for (status, schema) in errors {
+            responses.responses.insert(
+                oa::StatusCode::Code(status.as_u16()),
+                response(
+                    status.canonical_reason().unwrap_or("Error"),
+                    "application/json",
+                    schema,
+                ),
+            );
+        }
+        if let Some(schema) = &self.error_schema.default_schema {
+            responses.default = Some(response("Error", "application/json", schema));
+        }
+
+        oa::Operation {
+            tags: self.doc.tags.clone(),
+            summary: self.doc.summary.clone(),
+            description: self.doc.description.clone(),
+            request_body: self.request_schema.as_ref().map(|schema| {
+                oa::ReferenceOr::Item(oa::RequestBody {
+                    content: content("application/json", schema),
+                    required: true,
+                    ..Default::default()
+                })
+            }),
+            responses,
+            ..Default::default()
+        }
+    }
+}
+
+impl<T> Router<T>
+where
+    T: Send + Sync + 'static + ?Sized,
+{
+    /// Creates the router without any routes, which responds `404 Not Found` to every request.
+    pub fn new(app: Arc<T>) -> Self {
+        Self::from_handler(app, not_found::<T>)
+    }
+}
+
+fn not_found<T: ?Sized>(
+    _app: Arc<T>,
+    req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    let resp = match req.into_body() {
+        Ok(_) => error_response(&BaseError::NotFound),
+        Err(err) => error_response(&*err),
+    };
+
+    Box::pin(async move { Ok(resp) })
 }
 
 impl<T, H> Router<T, H>
@@ -49,6 +176,235 @@ where
         + Sync
         + 'static,
 {
+    pub fn from_handler(app: Arc<T>, handler: H) -> Self {
+        Router {
+            app,
+            handler,
+            routes: Default::default(),
+        }
+    }
+
+    /// Registers the typed handler for the given method and path.
+    ///
+    /// The request body is parsed as JSON into `Req`, and the `Resp` or the `E` is
+    /// rendered as the response. Their schemas are recorded in the [`Route`].
+    /// Requests which don't match the route are passed to the previous handler.
+    #[allow(clippy::type_complexity)]
+    pub fn route<Req, Resp, E, F, Fut>(
+        self,
+        method: SupportedMethod,
+        path: impl Into<Cow<'static, str>>,
+        handler: F,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    >
+    where
+        Req: Schema + Send,
+        Resp: Schema,
+        E: Error,
+        F: Fn(Arc<T>, Req) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, E>> + Send + 'static,
+    {
+        self.route_with_doc(method, path, OperationDoc::default(), handler)
+    }
+
+    /// Registers the typed handler like the [`route`](Router::route),
+    /// with the documentation of the operation.
+    #[allow(clippy::type_complexity)]
+    pub fn route_with_doc<Req, Resp, E, F, Fut>(
+        self,
+        method: SupportedMethod,
+        path: impl Into<Cow<'static, str>>,
+        doc: OperationDoc,
+        handler: F,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    >
+    where
+        Req: Schema + Send,
+        Resp: Schema,
+        E: Error,
+        F: Fn(Arc<T>, Req) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, E>> + Send + 'static,
+    {
+        let path = path.into();
+        let mut routes = self.routes;
+        Arc::make_mut(&mut routes).push(Route {
+            method,
+            path: path.clone(),
+            request_schema: if TypeId::of::<Req>() == TypeId::of::<()>() {
+                None
+            } else {
+                Some(Req::schema())
+            },
+            response_schema: Resp::schema(),
+            error_schema: E::error_schema(),
+            doc,
+            response_media_type: if TypeId::of::<Resp>() == TypeId::of::<String>() {
+                "text/plain"
+            } else {
+                "application/json"
+            },
+            validate_response: validate_response::<Resp>,
+        });
+        let fallback = self.handler;
+
+        Router {
+            app: self.app,
+            routes,
+            handler: move |app, req| {
+                let matched = SupportedMethod::new(req.method().clone()).ok() == Some(method)
+                    && req.uri().path() == path;
+                if !matched {
+                    return fallback(app, req);
+                }
+
+                let expect = ext::<ExpectJson, _>(&req).copied().unwrap_or_default();
+                let is_json = req
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .is_some_and(codec::is_json_content_type);
+                let body = match req.into_body() {
+                    Ok("") => codec::from_json_with::<Req>("null", &expect)
+                        .map_err(|_| codec::body_required()),
+                    Ok(_) if expect.require_content_type && !is_json => {
+                        Err(BaseError::UnsupportedMediaType)
+                    }
+                    Ok(body) => codec::from_json_with::<Req>(body, &expect),
+                    Err(err) => Err(*err),
+                };
+                let handler = handler.clone();
+
+                Box::pin(async move {
+                    let body = match body {
+                        Ok(body) => body,
+                        Err(err) => return Ok(error_response(&err)),
+                    };
+
+                    match handler(app, body).await {
+                        Ok(resp) => match (&resp as &dyn Any).downcast_ref::<String>() {
+                            Some(text) => {
+                                let mut resp = Response::new(text.clone());
+                                resp.headers_mut().insert(
+                                    CONTENT_TYPE,
+                                    HeaderValue::from_static("text/plain; charset=utf-8"),
+                                );
+                                Ok(resp)
+                            }
+                            None => codec::json_response(StatusCode::OK, &resp),
+                        },
+                        Err(err) => Ok(error_response(&err)),
+                    }
+                })
+            },
+        }
+    }
+
+    /// Overrides the [`Builder::expect_json`] for the routes of this router.
+    ///
+    /// Routes of the other routers [merged](Router::merge) into the same service
+    /// keep their own strictness.
+    #[allow(clippy::type_complexity)]
+    pub fn expect_json(
+        self,
+        expect: ExpectJson,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    > {
+        self.map_request(move |mut req| {
+            req.extensions_mut().insert(expect);
+            req
+        })
+    }
+
+    /// Serves the routes of the `other` router too, which must share the app of this router.
+    ///
+    /// Requests which match the routes of the `other` are passed to its handler,
+    /// and the rest to the handler of this router. So the fallback of the `other`
+    /// is never reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `other` router is made with another `Arc` of the app,
+    /// which would be dropped silently otherwise.
+    #[allow(clippy::type_complexity)]
+    pub fn merge<H2>(
+        self,
+        other: Router<T, H2>,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    >
+    where
+        H2: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        assert!(
+            Arc::ptr_eq(&self.app, &other.app),
+            "merged routers must share the same app"
+        );
+
+        let mut routes = self.routes;
+        Arc::make_mut(&mut routes).extend(other.routes.iter().cloned());
+        let other_routes = other.routes;
+        let other = other.handler;
+        let fallback = self.handler;
+
+        Router {
+            app: self.app,
+            routes,
+            handler: move |app, req| {
+                let method = SupportedMethod::new(req.method().clone()).ok();
+                let matched = other_routes
+                    .iter()
+                    .any(|route| Some(route.method) == method && route.path == req.uri().path());
+
+                if matched {
+                    other(app, req)
+                } else {
+                    fallback(app, req)
+                }
+            },
+        }
+    }
+
     pub fn with<F, H2>(self, middleware: F) -> Router<T, H2>
     where
         F: FnOnce(H) -> H2,
@@ -64,22 +420,289 @@ where
         Router {
             app: self.app,
             handler: middleware(self.handler),
+            routes: self.routes,
+        }
+    }
+
+    /// Adds the `headers` to the responses of this router's routes,
+    /// unless the handler has set them already.
+    ///
+    /// They take precedence over the [`Builder::default_response_headers`],
+    /// so the handler's headers win over the router's, and the router's over the service's.
+    #[allow(clippy::type_complexity)]
+    pub fn with_default_response_headers(
+        self,
+        headers: HeaderMap,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    > {
+        let headers = Arc::new(headers);
+        self.map_response(move |mut resp| {
+            match resp.extensions_mut().get_mut::<DefaultHeaders>() {
+                Some(DefaultHeaders(stashed)) => insert_default_headers(stashed, &headers),
+                None => {
+                    let stashed = DefaultHeaders(HeaderMap::clone(&headers));
+                    resp.extensions_mut().insert(stashed);
+                }
+            }
+            resp
+        })
+    }
+
+    /// Applies the middleware closure like the [`with`](Router::with),
+    /// under the name the tower users know.
+    pub fn layer_fn<F, H2>(self, middleware: F) -> Router<T, H2>
+    where
+        F: FnOnce(H) -> H2,
+        H2: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.with(middleware)
+    }
+
+    /// Applies the middleware type implementing the [`Middleware`].
+    #[allow(clippy::type_complexity)]
+    pub fn layer<M: Middleware<T>>(
+        self,
+        layer: M,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    > {
+        self.with(|handler| middleware::unboxed(layer.apply(middleware::boxed(handler))))
+    }
+
+    /// Applies the `middlewares` in the list order, the first one being the outermost.
+    ///
+    /// So the first middleware sees the request first and the response last,
+    /// like the `.with()` of the last one called first.
+    #[allow(clippy::type_complexity)]
+    pub fn fold_middleware(
+        self,
+        middlewares: Vec<Box<dyn Middleware<T>>>,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    > {
+        self.with(|handler| {
+            let handler = middlewares
+                .iter()
+                .rev()
+                .fold(middleware::boxed(handler), |handler, middleware| {
+                    middleware.apply(handler)
+                });
+            middleware::unboxed(handler)
+        })
+    }
+
+    /// Post-processes the response of the handler.
+    ///
+    /// Errors from the handler bypass the `mapper`.
+    #[allow(clippy::type_complexity)]
+    pub fn map_response<F>(
+        self,
+        mapper: F,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    >
+    where
+        F: Fn(Response<String>) -> Response<String> + Clone + Send + Sync + 'static,
+    {
+        let handler = self.handler;
+
+        Router {
+            app: self.app,
+            routes: self.routes,
+            handler: move |app, req| {
+                let resp = handler(app, req);
+                let mapper = mapper.clone();
+                Box::pin(async move { resp.await.map(mapper) })
+            },
         }
     }
 
+    /// Pre-processes the request before it reaches the handler.
+    #[allow(clippy::type_complexity)]
+    pub fn map_request<F>(
+        self,
+        mapper: F,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    >
+    where
+        F: for<'a> Fn(
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> Request<Result<&'a str, Box<BaseError>>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let handler = self.handler;
+
+        Router {
+            app: self.app,
+            handler: move |app, req| handler(app, mapper(req)),
+            routes: self.routes,
+        }
+    }
+
+    /// Runs the handler within the span built from the request by the `span`,
+    /// so every event of the handler carries its fields like the tenant or the user id.
+    ///
+    /// Apply it before the middlewares which insert the extensions it reads,
+    /// e.g. the [`Auth`](crate::middleware::Auth), so they run before it.
+    #[allow(clippy::type_complexity)]
+    pub fn with_span_fields<F>(
+        self,
+        span: F,
+    ) -> Router<
+        T,
+        impl for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    >
+    where
+        F: for<'a> Fn(&Request<Result<&'a str, Box<BaseError>>>) -> tracing::Span
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        use tracing::Instrument;
+
+        let handler = self.handler;
+
+        Router {
+            app: self.app,
+            routes: self.routes,
+            handler: move |app, req| {
+                let span = span(&req);
+                let resp = span.in_scope(|| handler(app, req));
+                Box::pin(resp.instrument(span))
+            },
+        }
+    }
+
+    /// Routes registered by the [`route`](Router::route), in the registration order.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Returns the registered routes sorted by the path and then the method.
+    pub fn debug_routes(&self) -> Vec<(SupportedMethod, String)> {
+        let mut routes: Vec<_> = self
+            .routes
+            .iter()
+            .map(|route| (route.method, route.path.to_string()))
+            .collect();
+        routes.sort_by(|(m1, p1), (m2, p2)| p1.cmp(p2).then(m1.cmp(m2)));
+        routes
+    }
+
     pub fn call<'a>(
         &self,
         request: Request<Result<&'a str, Box<BaseError>>>,
     ) -> BoxFuture<'a, Result<Response<String>, BoxError>> {
-        let app = Arc::clone(&self.app);
+        let resp = (self.handler)(Arc::clone(&self.app), request);
 
-        (self.handler)(app, request)
+        Box::pin(async move {
+            let mut resp = resp.await?;
+            insert_router_headers(&mut resp);
+            Ok(resp)
+        })
     }
 
-    pub async fn run(self, addr: SocketAddr) -> Result<(), BoxError> {
-        let service = Service::new(self);
-        Server::try_bind(&addr)?.serve(service).await?;
-        Ok(())
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn run(self, addr: SocketAddr) -> Result<(), RunError> {
+        Service::new(self).run(addr).await
+    }
+
+    /// Runs the server, sending the bound address to the `ready` once it accepts
+    /// the connections. See [`Service::run_with_ready`].
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn run_with_ready(
+        self,
+        addr: SocketAddr,
+        ready: tokio::sync::oneshot::Sender<SocketAddr>,
+    ) -> Result<(), RunError> {
+        Service::new(self).run_with_ready(addr, ready).await
+    }
+
+    /// Spawns the server onto the current runtime, instead of awaiting it.
+    ///
+    /// Returns the handle of the server task and the bound address.
+    #[cfg(feature = "tokio-runtime")]
+    pub fn try_run(
+        self,
+        addr: SocketAddr,
+    ) -> Result<(tokio::task::JoinHandle<Result<(), RunError>>, SocketAddr), RunError> {
+        Service::new(self).spawn(addr)
+    }
+
+    /// Runs the server over TLS with the `config`, see [`Service::run_tls`].
+    #[cfg(feature = "rustls")]
+    pub async fn run_tls(
+        self,
+        addr: SocketAddr,
+        config: tokio_rustls::rustls::ServerConfig,
+    ) -> Result<(), RunError> {
+        Service::new(self).run_tls(addr, config).await
+    }
+
+    /// Runs the server with the service configured by the `builder`.
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn run_with(self, addr: SocketAddr, builder: Builder) -> Result<(), RunError> {
+        builder.build(self).run(addr).await
     }
 }
 
@@ -99,6 +722,7 @@ where
         Self {
             app: Arc::clone(&self.app),
             handler: self.handler.clone(),
+            routes: Arc::clone(&self.routes),
         }
     }
 }
@@ -119,6 +743,433 @@ where
         f.debug_struct("Router")
             .field("app", &self.app)
             .field("handler", &"fn { ... }")
+            .field("routes", &self.routes)
             .finish()
     }
 }
+
+/// Prints the route table of the [`Router::debug_routes`], one route per line.
+impl<T, H> fmt::Display for Router<T, H>
+where
+    T: Send + Sync + 'static + ?Sized,
+    H: for<'a> Fn(
+            Arc<T>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (method, path) in self.debug_routes() {
+            writeln!(f, "{:<7} {}", method.as_ref(), path)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_response<Resp: Schema>(body: &str) -> Result<(), String> {
+    // `String` responses are sent as the plain text.
+    if TypeId::of::<Resp>() == TypeId::of::<String>() {
+        return Ok(());
+    }
+
+    codec::from_json::<Resp>(body)
+        .map(drop)
+        .map_err(|err| err.to_string())
+}
+
+/// Inserts each of the `defaults` with all its values, if the `headers` don't have it.
+pub(crate) fn insert_default_headers(headers: &mut HeaderMap, defaults: &HeaderMap) {
+    for name in defaults.keys() {
+        if headers.contains_key(name) {
+            continue;
+        }
+        for value in defaults.get_all(name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Headers of the [`Router::with_default_response_headers`], kept in the response
+/// extensions until the service has applied its own `Cache-Control` policy.
+struct DefaultHeaders(HeaderMap);
+
+/// Inserts the headers kept by the [`Router::with_default_response_headers`].
+pub(crate) fn insert_router_headers(resp: &mut Response<String>) {
+    if let Some(DefaultHeaders(defaults)) = resp.extensions_mut().remove() {
+        insert_default_headers(resp.headers_mut(), &defaults);
+    }
+}
+
+/// Returns the value of type `E` inserted into the request extensions.
+///
+/// Extensions of the incoming request are kept until it reaches the handler,
+/// along with the ones inserted by the [`Service`](crate::service::Service)
+/// like the [`ClientIp`](crate::service::ClientIp).
+pub fn ext<E, B>(req: &Request<B>) -> Option<&E>
+where
+    E: Send + Sync + 'static,
+{
+    req.extensions().get::<E>()
+}
+
+#[cfg(test)]
+pub(crate) fn echo_path(
+    _app: Arc<()>,
+    req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    let path = req.uri().path().to_owned();
+    Box::pin(async move {
+        if path == "/error" {
+            return Err("handler failed".into());
+        }
+        Ok(Response::new(path))
+    })
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn map_response_adds_header() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static MAPPED: AtomicUsize = AtomicUsize::new(0);
+
+    let router = Router::from_handler(Arc::new(()), echo_path).map_response(|mut resp| {
+        MAPPED.fetch_add(1, Ordering::SeqCst);
+        resp.headers_mut()
+            .insert("x-mapped", http::HeaderValue::from_static("yes"));
+        resp
+    });
+
+    let req = Request::get("/foo").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.body(), "/foo");
+    assert_eq!(resp.headers()["x-mapped"], "yes");
+    assert_eq!(MAPPED.load(Ordering::SeqCst), 1);
+
+    let req = Request::get("/error").body(Ok("")).unwrap();
+    assert!(router.call(req).await.is_err());
+    assert_eq!(MAPPED.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn map_request_injects_header() {
+    fn echo_header(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let value = req.headers()["x-injected"].to_str().unwrap().to_owned();
+        Box::pin(async move { Ok(Response::new(value)) })
+    }
+
+    let router = Router::from_handler(Arc::new(()), echo_header).map_request(|mut req| {
+        req.headers_mut()
+            .insert("x-injected", http::HeaderValue::from_static("default"));
+        req
+    });
+
+    let req = Request::get("/").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.body(), "default");
+}
+
+#[test]
+fn debug_routes_are_sorted() {
+    async fn noop(_app: Arc<()>, _body: ()) -> Result<(), String> {
+        Ok(())
+    }
+
+    let router = Router::new(Arc::new(()))
+        .route(SupportedMethod::Post, "/users", noop)
+        .route(SupportedMethod::Get, "/health", noop)
+        .route(SupportedMethod::Get, "/users", noop)
+        .route(SupportedMethod::Delete, "/users/{id}", noop);
+
+    assert_eq!(
+        router.debug_routes(),
+        vec![
+            (SupportedMethod::Get, "/health".to_owned()),
+            (SupportedMethod::Get, "/users".to_owned()),
+            (SupportedMethod::Post, "/users".to_owned()),
+            (SupportedMethod::Delete, "/users/{id}".to_owned()),
+        ]
+    );
+    assert_eq!(
+        router.to_string(),
+        "GET     /health\nGET     /users\nPOST    /users\nDELETE  /users/{id}\n"
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn route_records_schemas() {
+    async fn double(_app: Arc<()>, body: Vec<u32>) -> Result<Vec<u32>, String> {
+        Ok(body.into_iter().map(|n| n * 2).collect())
+    }
+
+    let router = Router::new(Arc::new(())).route(SupportedMethod::Post, "/double", double);
+
+    assert_eq!(router.routes.len(), 1);
+    let route = &router.routes[0];
+    assert_eq!(route.method, SupportedMethod::Post);
+    assert_eq!(route.path, "/double");
+    assert_eq!(route.request_schema, Some(<Vec<u32>>::schema()));
+    assert_eq!(route.response_schema, <Vec<u32>>::schema());
+
+    let req = Request::post("/double").body(Ok("[1, 2]")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "[2,4]");
+
+    let req = Request::post("/double").body(Ok("{}")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = Request::post("/double").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_str(resp.body()).unwrap();
+    assert_eq!(
+        body["InvalidParameter"]["body"][0]["reason"],
+        "request body is required"
+    );
+
+    let req = Request::get("/double").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+#[tokio::test]
+async fn try_run_returns_handle() {
+    let router = Router::from_handler(Arc::new(()), echo_path);
+    let (handle, addr) = router.try_run(([127, 0, 0, 1], 0).into()).unwrap();
+    assert_ne!(addr.port(), 0);
+
+    let uri: http::Uri = format!("http://{}/spawned", addr).parse().unwrap();
+    let resp = hyper::Client::new().get(uri).await.unwrap();
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "/spawned");
+
+    handle.abort();
+    assert!(handle.await.unwrap_err().is_cancelled());
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[test]
+fn route_with_doc_fills_operation() {
+    async fn list(_app: Arc<()>, _body: ()) -> Result<Vec<String>, BaseError> {
+        Ok(vec![])
+    }
+
+    let doc = OperationDoc {
+        summary: Some("List the names".into()),
+        description: None,
+        tags: vec!["names".into()],
+    };
+    let router = Router::new(Arc::new(()))
+        .route_with_doc(SupportedMethod::Get, "/names", doc, list)
+        .route(SupportedMethod::Post, "/names", list);
+
+    let operation = router.routes[0].operation();
+    assert_eq!(operation.summary.as_deref(), Some("List the names"));
+    assert_eq!(operation.tags, ["names"]);
+    assert!(operation.request_body.is_none());
+    assert!(operation
+        .responses
+        .responses
+        .contains_key(&oa::StatusCode::Code(200)));
+    assert!(operation
+        .responses
+        .responses
+        .contains_key(&oa::StatusCode::Code(404)));
+
+    let operation = router.routes[1].operation();
+    assert_eq!(operation.summary, None);
+    assert!(operation.tags.is_empty());
+}
+
+#[test]
+fn string_response_is_text_plain() {
+    async fn greet(_app: Arc<()>, _body: ()) -> Result<String, BaseError> {
+        Ok("hello".into())
+    }
+
+    let router = Router::new(Arc::new(())).route(SupportedMethod::Get, "/greet", greet);
+    let operation = router.routes()[0].operation();
+    let ok = match &operation.responses.responses[&oa::StatusCode::Code(200)] {
+        oa::ReferenceOr::Item(resp) => resp,
+        oa::ReferenceOr::Reference { .. } => panic!("unexpected reference"),
+    };
+    assert_eq!(ok.content.keys().collect::<Vec<_>>(), ["text/plain"]);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn span_fields_follow_auth() {
+    use crate::middleware::Auth;
+    use crate::test_util::Captured;
+
+    #[derive(Clone)]
+    struct Tenant(&'static str);
+
+    fn handle(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async {
+            tracing::info!("handled");
+            Ok(Response::new(String::new()))
+        })
+    }
+
+    let captured = Captured::default();
+    let _guard = tracing::subscriber::set_default(captured.subscriber(tracing::Level::INFO));
+
+    let router = Router::from_handler(Arc::new(()), handle)
+        .with_span_fields(|req: &Request<Result<&str, Box<BaseError>>>| {
+            let tenant = ext::<Tenant, _>(req).map_or("unknown", |tenant| tenant.0);
+            tracing::info_span!("request", tenant)
+        })
+        .with(|handler| {
+            Auth::new(|_: &Request<Result<&str, Box<BaseError>>>| Ok(Tenant("acme"))).wrap(handler)
+        });
+    router
+        .call(Request::get("/").body(Ok("")).unwrap())
+        .await
+        .unwrap();
+
+    let log = captured.log();
+    assert!(log.contains("request{tenant=\"acme\"}: "), "{}", log);
+    assert!(log.contains("handled"), "{}", log);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn fold_middleware_applies_in_list_order() {
+    use crate::middleware::{boxed, BoxHandler, CatchPanic};
+    use std::sync::Mutex;
+
+    let log = Arc::new(Mutex::new(vec![]));
+    let record = |name: &'static str| -> Box<dyn Middleware<()>> {
+        let log = Arc::clone(&log);
+        Box::new(move |inner: BoxHandler<()>| {
+            let log = Arc::clone(&log);
+            boxed(move |app, req| {
+                log.lock().unwrap().push(name);
+                inner(app, req)
+            })
+        })
+    };
+
+    let router = Router::from_handler(Arc::new(()), echo_path).fold_middleware(vec![
+        record("first"),
+        Box::new(CatchPanic::new()),
+        record("second"),
+        record("third"),
+    ]);
+    let resp = router
+        .call(Request::get("/path").body(Ok("")).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(resp.body(), "/path");
+    assert_eq!(*log.lock().unwrap(), ["first", "second", "third"]);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn merged_routers_keep_their_json_strictness() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    impl Schema for Item {
+        fn schema() -> oa::Schema {
+            String::schema()
+        }
+    }
+
+    async fn name(_app: Arc<()>, item: Item) -> Result<String, String> {
+        Ok(item.name)
+    }
+
+    let app = Arc::new(());
+    let strict = Router::new(Arc::clone(&app))
+        .route(SupportedMethod::Post, "/strict", name)
+        .expect_json(ExpectJson::strict());
+    let lenient = Router::new(app)
+        .route(SupportedMethod::Post, "/lenient", name)
+        .expect_json(ExpectJson::default());
+    let router = strict.merge(lenient);
+    assert_eq!(router.routes.len(), 2);
+
+    let call = |path: &'static str, content_type: Option<&'static str>| {
+        let mut req = Request::post(path);
+        if let Some(content_type) = content_type {
+            req = req.header(CONTENT_TYPE, content_type);
+        }
+        let req = req.body(Ok(r#"{"name": "a", "extra": 1}"#)).unwrap();
+        router.call(req)
+    };
+
+    let resp = call("/strict", Some("application/json")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = call("/lenient", Some("application/json")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "a");
+
+    let resp = call("/strict", None).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let resp = call("/lenient", Some("text/plain")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = call("/missing", None).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+#[should_panic(expected = "merged routers must share the same app")]
+fn merge_rejects_another_app() {
+    let first = Router::from_handler(Arc::new(()), echo_path);
+    let second = Router::from_handler(Arc::new(()), echo_path);
+    drop(first.merge(second));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn layer_applies_middleware_types() {
+    use middleware::BoxHandler;
+
+    struct AddHeader(&'static str);
+
+    impl<T: Send + Sync + 'static + ?Sized> Middleware<T> for AddHeader {
+        fn apply(&self, handler: BoxHandler<T>) -> BoxHandler<T> {
+            let value = HeaderValue::from_static(self.0);
+            Arc::new(move |app, req| {
+                let resp = handler(app, req);
+                let value = value.clone();
+                Box::pin(async move {
+                    let mut resp = resp.await?;
+                    resp.headers_mut().insert("x-layer", value);
+                    Ok(resp)
+                })
+            })
+        }
+    }
+
+    let router = Router::from_handler(Arc::new(()), echo_path)
+        .layer(AddHeader("inner"))
+        .layer_fn(|handler| handler)
+        .layer(AddHeader("outer"));
+
+    let req = Request::get("/foo").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.body(), "/foo");
+    assert_eq!(resp.headers()["x-layer"], "outer");
+}
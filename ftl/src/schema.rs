@@ -1,14 +1,199 @@
 use std::any::Any;
+use std::borrow::Cow;
 use std::cmp::{Eq, Ord};
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
-use std::hash::Hash;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList};
+use std::hash::{BuildHasher, Hash};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::num::{
+    NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8,
+    Saturating, Wrapping,
+};
+use std::sync::atomic::{
+    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64, AtomicU8,
+};
+use std::time::SystemTime;
 
+use indexmap::IndexMap;
 use openapiv3 as oa;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, Map, Value};
 
+#[doc(hidden)]
+pub mod derive;
+
 pub trait Schema: Any + Serialize + DeserializeOwned {
     fn schema() -> oa::Schema;
+
+    /// Name of this type within the `components/schemas` of the OpenAPI document.
+    ///
+    /// Types without the name are always inlined.
+    fn schema_name() -> Option<Cow<'static, str>> {
+        None
+    }
+
+    /// Registers the named schemas this type refers to.
+    fn register_components(_registry: &mut SchemaRegistry) {}
+
+    /// Pattern of the `Display` output, used by the [`AsString`](crate::types::AsString).
+    ///
+    /// If `None`, it's derived from the integer and number schemas.
+    fn string_pattern() -> Option<&'static str> {
+        None
+    }
+}
+
+/// Returns `$ref` to the component if `T` is named, or the inlined schema otherwise.
+pub fn reference<T: Schema>() -> oa::ReferenceOr<Box<oa::Schema>> {
+    match T::schema_name() {
+        Some(name) => oa::ReferenceOr::ref_(&format!("#/components/schemas/{}", name)),
+        None => oa::ReferenceOr::Item(Box::new(T::schema())),
+    }
+}
+
+/// Like the [`reference`], for the places where the schema isn't boxed.
+pub(crate) fn reference_unboxed<T: Schema>() -> oa::ReferenceOr<oa::Schema> {
+    match T::schema_name() {
+        Some(name) => oa::ReferenceOr::ref_(&format!("#/components/schemas/{}", name)),
+        None => oa::ReferenceOr::Item(T::schema()),
+    }
+}
+
+/// Accumulates the named component schemas for the OpenAPI document.
+///
+/// Each named type is registered exactly once,
+/// along with every named type it refers to.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: IndexMap<String, oa::Schema>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn register<T: Schema>(&mut self) -> &mut Self {
+        if let Some(name) = T::schema_name() {
+            if self.schemas.contains_key(&*name) {
+                return self;
+            }
+
+            self.schemas.insert(name.into_owned(), T::schema());
+        }
+
+        T::register_components(self);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&oa::Schema> {
+        self.schemas.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    pub fn components(&self) -> oa::Components {
+        let mut components = oa::Components::default();
+        self.merge_into_components(&mut components);
+        components
+    }
+
+    /// Adds registered schemas to the existing components, keeping the ones already there.
+    pub fn merge_into_components(&self, components: &mut oa::Components) {
+        for (name, schema) in &self.schemas {
+            components
+                .schemas
+                .entry(name.clone())
+                .or_insert_with(|| oa::ReferenceOr::Item(schema.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod registry_fixture {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Inner {
+        pub value: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Outer {
+        pub first: Inner,
+        pub rest: Vec<Inner>,
+    }
+
+    impl Schema for Inner {
+        fn schema() -> oa::Schema {
+            oa::Schema {
+                schema_data: Default::default(),
+                schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+                    properties: Some(("value".into(), reference::<u32>()))
+                        .into_iter()
+                        .collect(),
+                    required: vec!["value".into()],
+                    ..Default::default()
+                })),
+            }
+        }
+
+        fn schema_name() -> Option<Cow<'static, str>> {
+            Some("Inner".into())
+        }
+    }
+
+    impl Schema for Outer {
+        fn schema() -> oa::Schema {
+            oa::Schema {
+                schema_data: Default::default(),
+                schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+                    properties: vec![
+                        ("first".into(), reference::<Inner>()),
+                        ("rest".into(), reference::<Vec<Inner>>()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    required: vec!["first".into(), "rest".into()],
+                    ..Default::default()
+                })),
+            }
+        }
+
+        fn schema_name() -> Option<Cow<'static, str>> {
+            Some("Outer".into())
+        }
+
+        fn register_components(registry: &mut SchemaRegistry) {
+            registry.register::<Inner>().register::<Vec<Inner>>();
+        }
+    }
+}
+
+#[test]
+fn registry_registers_nested_once() {
+    use registry_fixture::{Inner, Outer};
+
+    let mut registry = SchemaRegistry::new();
+    registry.register::<Outer>().register::<Inner>();
+
+    let components = registry.components();
+    let names: Vec<_> = components.schemas.keys().map(String::as_str).collect();
+    assert_eq!(names, ["Outer", "Inner"]);
+
+    match &registry.get("Outer").unwrap().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(obj)) => assert_eq!(
+            obj.properties["first"],
+            oa::ReferenceOr::ref_("#/components/schemas/Inner"),
+        ),
+        other => panic!("unexpected schema {:?}", other),
+    }
 }
 
 #[cfg(test)]
@@ -21,14 +206,140 @@ impl<T: Schema> Schema for Box<T> {
     fn schema() -> oa::Schema {
         T::schema()
     }
+
+    fn schema_name() -> Option<Cow<'static, str>> {
+        T::schema_name()
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+#[test]
+fn parse_example_cow_slice() {
+    parse_example::<Cow<'static, [u32]>>();
+    assert_eq!(<Cow<'static, [u32]>>::schema(), <Vec<u32>>::schema());
+
+    let borrowed: Cow<'static, [u32]> = Cow::Borrowed(&[1, 2]);
+    let owned: Cow<'static, [u32]> = Cow::Owned(vec![1, 2]);
+    assert_eq!(
+        serde_json::to_value(borrowed).unwrap(),
+        serde_json::to_value(owned).unwrap()
+    );
 }
 
+/// Borrowed values share the schema of their owned form, like the `Cow<'static, str>`
+/// or the `Cow<'static, [T]>` which is the array of the `Vec<T>`.
+impl<B> Schema for Cow<'static, B>
+where
+    B: ToOwned + ?Sized + 'static,
+    B::Owned: Schema,
+    Self: Serialize + DeserializeOwned,
+{
+    fn schema() -> oa::Schema {
+        B::Owned::schema()
+    }
+
+    fn schema_name() -> Option<Cow<'static, str>> {
+        B::Owned::schema_name()
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<B::Owned>();
+    }
+}
+
+/// Named types are referred with the `allOf`, since the `$ref` can't be nullable.
 impl<T: Schema> Schema for Option<T> {
     fn schema() -> oa::Schema {
+        if T::schema_name().is_some() {
+            return oa::Schema {
+                schema_data: oa::SchemaData {
+                    nullable: true,
+                    ..Default::default()
+                },
+                schema_kind: oa::SchemaKind::AllOf {
+                    all_of: vec![reference_unboxed::<T>()],
+                },
+            };
+        }
+
         let mut schema = T::schema();
         schema.schema_data.nullable = true;
         schema
     }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+/// Schema of the base64 encoded byte string.
+pub(crate) fn byte_schema(title: &str) -> oa::Schema {
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: Some("Base64 encoded bytes".into()),
+            example: Some(json!("Zm9vYmFy")),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+            format: oa::VariantOrUnknownOrEmpty::Item(oa::StringFormat::Byte),
+            ..Default::default()
+        })),
+    }
+}
+
+#[test]
+fn parse_example_wrapping_u32() {
+    parse_example::<Wrapping<u32>>();
+
+    let schema = <Wrapping<u32>>::schema();
+    assert_eq!(schema.schema_kind, u32::schema().schema_kind);
+    assert_eq!(
+        schema.schema_data.example,
+        u32::schema().schema_data.example
+    );
+}
+
+impl<T: Schema> Schema for Wrapping<T>
+where
+    Wrapping<T>: Serialize + DeserializeOwned,
+{
+    fn schema() -> oa::Schema {
+        let mut schema = T::schema();
+        schema.schema_data.title = Some("Wrapping".into());
+        schema.schema_data.description =
+            Some("Integer with wrapping arithmetic, overflow wraps around".into());
+        schema
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+#[test]
+fn parse_example_saturating_u32() {
+    parse_example::<Saturating<u32>>()
+}
+
+impl<T: Schema> Schema for Saturating<T>
+where
+    Saturating<T>: Serialize + DeserializeOwned,
+{
+    fn schema() -> oa::Schema {
+        let mut schema = T::schema();
+        schema.schema_data.title = Some("Saturating".into());
+        schema.schema_data.description =
+            Some("Integer with saturating arithmetic, overflow clamps to the bounds".into());
+        schema
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
 }
 
 #[test]
@@ -36,6 +347,21 @@ fn parse_example_vec_u32() {
     parse_example::<Vec<u32>>()
 }
 
+#[test]
+fn parse_example_vec_u8() {
+    parse_example::<Vec<u8>>();
+
+    match <Vec<u8>>::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Array(ty)) => {
+            assert_eq!(ty.items, reference::<u8>());
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+/// Array of the items. Note that the `Vec<u8>` is an array of integers too,
+/// as the serde_json serializes it. Use the [`Base64Bytes`](crate::types::Base64Bytes)
+/// for the base64 string.
 impl<T: Schema> Schema for Vec<T> {
     fn schema() -> oa::Schema {
         oa::Schema {
@@ -46,13 +372,45 @@ impl<T: Schema> Schema for Vec<T> {
                 ..Default::default()
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType {
-                items: oa::ReferenceOr::Item(Box::new(T::schema())),
+                items: reference::<T>(),
                 min_items: None,
                 max_items: None,
                 unique_items: false,
             })),
         }
     }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+#[test]
+fn parse_example_linked_list_u32() {
+    parse_example::<LinkedList<u32>>()
+}
+
+impl<T: Schema> Schema for LinkedList<T> {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("LinkedList".into()),
+                description: Some("LinkedList".into()),
+                example: Some(json!([])),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType {
+                items: reference::<T>(),
+                min_items: None,
+                max_items: None,
+                unique_items: false,
+            })),
+        }
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
 }
 
 #[test]
@@ -60,7 +418,11 @@ fn parse_example_hashset_u32() {
     parse_example::<HashSet<u32>>()
 }
 
-impl<T: Schema + Eq + Hash> Schema for HashSet<T> {
+impl<T, S> Schema for HashSet<T, S>
+where
+    T: Schema + Eq + Hash,
+    S: BuildHasher + Default + 'static,
+{
     fn schema() -> oa::Schema {
         oa::Schema {
             schema_data: oa::SchemaData {
@@ -70,13 +432,17 @@ impl<T: Schema + Eq + Hash> Schema for HashSet<T> {
                 ..Default::default()
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType {
-                items: oa::ReferenceOr::Item(Box::new(T::schema())),
+                items: reference::<T>(),
                 min_items: None,
                 max_items: None,
                 unique_items: true,
             })),
         }
     }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
 }
 
 #[test]
@@ -94,13 +460,17 @@ impl<T: Schema + Ord> Schema for BTreeSet<T> {
                 ..Default::default()
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType {
-                items: oa::ReferenceOr::Item(Box::new(T::schema())),
+                items: reference::<T>(),
                 min_items: None,
                 max_items: None,
                 unique_items: true,
             })),
         }
     }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
 }
 
 #[test]
@@ -108,22 +478,49 @@ fn parse_example_hashmap_u32() {
     parse_example::<HashMap<String, u32>>()
 }
 
-impl<T: Schema> Schema for HashMap<String, T> {
-    fn schema() -> oa::Schema {
-        oa::Schema {
-            schema_data: oa::SchemaData {
-                title: Some("HashMap".into()),
-                description: Some("HashMap".into()),
-                example: Some(json!({})),
-                ..Default::default()
+#[test]
+fn parse_example_nested_maps() {
+    parse_example::<HashMap<String, Option<Vec<u32>>>>();
+    parse_example::<BTreeMap<String, Box<String>>>();
+    parse_example::<BTreeMap<String, Cow<'static, str>>>();
+    parse_example::<
+        HashMap<
+            String,
+            u32,
+            std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>,
+        >,
+    >();
+
+    match <HashMap<String, Option<Vec<u32>>>>::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => match object.additional_properties {
+            Some(oa::AdditionalProperties::Schema(value)) => match *value {
+                oa::ReferenceOr::Item(value) => {
+                    assert!(value.schema_data.nullable);
+                    assert!(matches!(
+                        value.schema_kind,
+                        oa::SchemaKind::Type(oa::Type::Array(_))
+                    ));
+                }
+                other => panic!("unexpected value {:?}", other),
             },
-            schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
-                additional_properties: Some(oa::AdditionalProperties::Schema(Box::new(
-                    oa::ReferenceOr::Item(T::schema()),
-                ))),
-                ..Default::default()
-            })),
-        }
+            other => panic!("unexpected additional properties {:?}", other),
+        },
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+impl<K, T, S> Schema for HashMap<K, T, S>
+where
+    K: SchemaKey + Eq + Hash,
+    T: Schema,
+    S: BuildHasher + Default + 'static,
+{
+    fn schema() -> oa::Schema {
+        map_schema::<K, T>("HashMap")
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
     }
 }
 
@@ -132,25 +529,67 @@ fn parse_example_btreemap_u32() {
     parse_example::<BTreeMap<String, u32>>()
 }
 
-impl<T: Schema> Schema for BTreeMap<String, T> {
+impl<K, T> Schema for BTreeMap<K, T>
+where
+    K: SchemaKey + Ord,
+    T: Schema,
+{
     fn schema() -> oa::Schema {
-        oa::Schema {
-            schema_data: oa::SchemaData {
-                title: Some("BTreeMap".into()),
-                description: Some("BTreeMap".into()),
-                example: Some(json!({})),
-                ..Default::default()
-            },
-            schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
-                additional_properties: Some(oa::AdditionalProperties::Schema(Box::new(
-                    oa::ReferenceOr::Item(T::schema()),
-                ))),
-                ..Default::default()
-            })),
+        map_schema::<K, T>("BTreeMap")
+    }
+
+    fn register_components(registry: &mut SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+/// Types which can be the keys of the maps, serialized as strings.
+///
+/// Implement it for the enums whose schema is the string `enum`, which the `Schema` derive
+/// does on its own, so their maps list the permitted keys.
+pub trait SchemaKey: Schema {
+    /// Permitted keys, or `None` if any string is accepted.
+    fn keys() -> Option<Vec<String>> {
+        match Self::schema().schema_kind {
+            oa::SchemaKind::Type(oa::Type::String(string)) if !string.enumeration.is_empty() => {
+                Some(string.enumeration)
+            }
+            _ => None,
         }
     }
 }
 
+impl SchemaKey for String {}
+
+/// Object of the `T` values, with the permitted keys in the description.
+/// The OpenAPI 3.0 has no `propertyNames` to constrain them.
+fn map_schema<K: SchemaKey, T: Schema>(title: &str) -> oa::Schema {
+    let keys = K::keys();
+    let example = match (&keys, T::schema().schema_data.example) {
+        (Some(keys), Some(value)) if !keys.is_empty() => json!({ keys[0].clone(): value }),
+        _ => json!({}),
+    };
+    let description = match keys {
+        Some(keys) => format!("{} with the keys: {}", title, keys.join(", ")),
+        None => title.into(),
+    };
+
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: Some(description),
+            example: Some(example),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+            additional_properties: Some(oa::AdditionalProperties::Schema(Box::new(
+                reference_unboxed::<T>(),
+            ))),
+            ..Default::default()
+        })),
+    }
+}
+
 impl Schema for Value {
     fn schema() -> oa::Schema {
         oa::Schema {
@@ -190,6 +629,108 @@ impl Schema for Map<String, Value> {
     }
 }
 
+#[test]
+fn parse_example_unit() {
+    parse_example::<()>()
+}
+
+impl Schema for () {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                nullable: true,
+                title: Some("()".into()),
+                description: Some("Unit, always null".into()),
+                example: Some(json!(null)),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Any(Default::default()),
+        }
+    }
+}
+
+/// Tuples are the arrays of the fixed length.
+///
+/// The OpenAPI 3.1 models them with the `prefixItems`, but this crate emits the 3.0
+/// where it's unavailable. So the elements are described as the `anyOf` of every element types,
+/// which loses their positions. It's not the `oneOf` since a value may match several elements,
+/// like the `(u32, u64)`.
+macro_rules! tuple_schema {
+    ($($len:literal => ($($elem:ident),+),)*) => {$(
+        impl<$($elem: Schema),+> Schema for ($($elem,)+) {
+            fn schema() -> oa::Schema {
+                oa::Schema {
+                    schema_data: oa::SchemaData {
+                        title: Some(concat!("Tuple", $len).into()),
+                        description: Some(concat!("Tuple of ", $len, " elements").into()),
+                        example: Some(json!([$($elem::schema().schema_data.example),+])),
+                        ..Default::default()
+                    },
+                    schema_kind: oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType {
+                        items: oa::ReferenceOr::Item(Box::new(oa::Schema {
+                            schema_data: Default::default(),
+                            schema_kind: oa::SchemaKind::AnyOf {
+                                any_of: vec![$(reference_unboxed::<$elem>()),+],
+                            },
+                        })),
+                        min_items: Some($len),
+                        max_items: Some($len),
+                        unique_items: false,
+                    })),
+                }
+            }
+
+            fn register_components(registry: &mut SchemaRegistry) {
+                $(registry.register::<$elem>();)+
+            }
+        }
+    )*};
+}
+
+tuple_schema! {
+    1 => (A),
+    2 => (A, B),
+    3 => (A, B, C),
+    4 => (A, B, C, D),
+    5 => (A, B, C, D, E),
+    6 => (A, B, C, D, E, F),
+}
+
+#[test]
+fn parse_example_tuple() {
+    parse_example::<(u32,)>();
+    parse_example::<(u32, String, bool)>();
+
+    let array = match <(u32, String, bool)>::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Array(array)) => array,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    assert_eq!((array.min_items, array.max_items), (Some(3), Some(3)));
+
+    let any_of = match array.items {
+        oa::ReferenceOr::Item(items) => match items.schema_kind {
+            oa::SchemaKind::AnyOf { any_of } => any_of,
+            other => panic!("unexpected items {:?}", other),
+        },
+        other => panic!("unexpected items {:?}", other),
+    };
+    let kinds: Vec<_> = any_of
+        .into_iter()
+        .map(|elem| match elem {
+            oa::ReferenceOr::Item(elem) => elem.schema_kind,
+            other => panic!("unexpected element {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            u32::schema().schema_kind,
+            String::schema().schema_kind,
+            bool::schema().schema_kind,
+        ]
+    );
+}
+
 #[test]
 fn parse_example_bool() {
     parse_example::<bool>()
@@ -226,7 +767,7 @@ impl Schema for u8 {
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int32),
                 minimum: Some(0),
-                maximum: Some(u8::max_value() as _),
+                maximum: Some(u8::MAX as _),
                 ..Default::default()
             })),
         }
@@ -250,7 +791,7 @@ impl Schema for u16 {
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int32),
                 minimum: Some(0),
-                maximum: Some(u16::max_value() as _),
+                maximum: Some(u16::MAX as _),
                 ..Default::default()
             })),
         }
@@ -274,7 +815,30 @@ impl Schema for u32 {
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int64),
                 minimum: Some(0),
-                maximum: Some(u32::max_value() as _),
+                maximum: Some(u32::MAX as _),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[test]
+fn parse_example_u64() {
+    parse_example::<u64>()
+}
+
+impl Schema for u64 {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("u64".into()),
+                description: Some("u64".into()),
+                example: Some(json!(1)),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
+                format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int64),
+                minimum: Some(0),
                 ..Default::default()
             })),
         }
@@ -297,8 +861,8 @@ impl Schema for i8 {
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int32),
-                minimum: Some(i8::min_value() as _),
-                maximum: Some(i8::max_value() as _),
+                minimum: Some(i8::MIN as _),
+                maximum: Some(i8::MAX as _),
                 ..Default::default()
             })),
         }
@@ -321,8 +885,8 @@ impl Schema for i16 {
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int32),
-                minimum: Some(i16::min_value() as _),
-                maximum: Some(i16::max_value() as _),
+                minimum: Some(i16::MIN as _),
+                maximum: Some(i16::MAX as _),
                 ..Default::default()
             })),
         }
@@ -345,8 +909,8 @@ impl Schema for i32 {
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int32),
-                minimum: Some(i32::min_value() as _),
-                maximum: Some(i32::max_value() as _),
+                minimum: Some(i32::MIN as _),
+                maximum: Some(i32::MAX as _),
                 ..Default::default()
             })),
         }
@@ -369,14 +933,99 @@ impl Schema for i64 {
             },
             schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
                 format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int64),
-                minimum: Some(i64::min_value()),
-                maximum: Some(i64::max_value()),
+                minimum: Some(i64::MIN),
+                maximum: Some(i64::MAX),
                 ..Default::default()
             })),
         }
     }
 }
 
+macro_rules! atomic_schema {
+    ($($atomic:ident => $int:ty,)*) => {$(
+        impl Schema for $atomic {
+            fn schema() -> oa::Schema {
+                let mut schema = <$int>::schema();
+                schema.schema_data.title = Some(stringify!($atomic).into());
+                schema.schema_data.description = Some(stringify!($atomic).into());
+                schema
+            }
+        }
+    )*};
+}
+
+atomic_schema! {
+    AtomicU8 => u8,
+    AtomicU16 => u16,
+    AtomicU32 => u32,
+    AtomicU64 => u64,
+    AtomicI8 => i8,
+    AtomicI16 => i16,
+    AtomicI32 => i32,
+    AtomicI64 => i64,
+}
+
+/// Unsigned ones have the `minimum: 1`. The OpenAPI 3.0 can't exclude the zero alone,
+/// so the signed ones only tell it in the description.
+macro_rules! non_zero_schema {
+    ($($non_zero:ident => $int:ty,)*) => {$(
+        impl Schema for $non_zero {
+            fn schema() -> oa::Schema {
+                let mut schema = <$int>::schema();
+                schema.schema_data.title = Some(stringify!($non_zero).into());
+                schema.schema_data.description =
+                    Some(concat!("Non-zero ", stringify!($int)).into());
+                if let oa::SchemaKind::Type(oa::Type::Integer(int)) = &mut schema.schema_kind {
+                    if int.minimum == Some(0) {
+                        int.minimum = Some(1);
+                    }
+                }
+                schema
+            }
+        }
+    )*};
+}
+
+non_zero_schema! {
+    NonZeroU8 => u8,
+    NonZeroU16 => u16,
+    NonZeroU32 => u32,
+    NonZeroU64 => u64,
+    NonZeroI8 => i8,
+    NonZeroI16 => i16,
+    NonZeroI32 => i32,
+    NonZeroI64 => i64,
+}
+
+#[test]
+fn parse_example_option_non_zero_u32() {
+    parse_example::<NonZeroU32>();
+    parse_example::<Option<NonZeroU32>>();
+    parse_example::<NonZeroI64>();
+
+    let schema = <Option<NonZeroU32>>::schema();
+    assert!(schema.schema_data.nullable);
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Integer(int)) => {
+            assert_eq!((int.minimum, int.maximum), (Some(1), Some(u32::MAX as _)));
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+
+    let none: Option<NonZeroU32> = serde_json::from_value(Value::Null).unwrap();
+    assert_eq!(none, None);
+    assert!(serde_json::from_value::<Option<NonZeroU32>>(json!(0)).is_err());
+}
+
+#[test]
+fn parse_example_atomic_u32() {
+    parse_example::<AtomicU32>();
+
+    let schema = AtomicU32::schema();
+    assert_eq!(schema.schema_kind, u32::schema().schema_kind);
+    assert_eq!(schema.schema_data.example, Some(json!(1)));
+}
+
 #[test]
 fn parse_example_f32() {
     parse_example::<f32>()
@@ -415,6 +1064,30 @@ impl Schema for f64 {
     }
 }
 
+#[cfg(feature = "half")]
+#[test]
+fn parse_example_f16() {
+    parse_example::<half::f16>();
+
+    let example = half::f16::schema().schema_data.example.unwrap();
+    let value: half::f16 = serde_json::from_value(example).unwrap();
+    assert_eq!(value, half::f16::ONE);
+}
+
+/// The `half` serializes the `f16` as the `u16` of its IEEE 754 bits,
+/// so it's not the JSON number.
+#[cfg(feature = "half")]
+impl Schema for half::f16 {
+    fn schema() -> oa::Schema {
+        let mut schema = u16::schema();
+        schema.schema_data.title = Some("f16".into());
+        schema.schema_data.description =
+            Some("Half-precision f16 as the bits, with about 3 significant decimal digits".into());
+        schema.schema_data.example = Some(json!(half::f16::ONE.to_bits()));
+        schema
+    }
+}
+
 #[test]
 fn parse_example_string() {
     parse_example::<String>()
@@ -433,3 +1106,378 @@ impl Schema for String {
         }
     }
 }
+
+#[cfg(feature = "compact_str")]
+#[test]
+fn parse_example_compact_string() {
+    parse_example::<compact_str::CompactString>()
+}
+
+#[cfg(feature = "compact_str")]
+impl Schema for compact_str::CompactString {
+    fn schema() -> oa::Schema {
+        let mut schema = String::schema();
+        schema.schema_data.title = Some("CompactString".into());
+        schema
+    }
+}
+
+#[cfg(feature = "smol_str")]
+#[test]
+fn parse_example_smol_str() {
+    parse_example::<smol_str::SmolStr>()
+}
+
+#[cfg(feature = "smol_str")]
+impl Schema for smol_str::SmolStr {
+    fn schema() -> oa::Schema {
+        let mut schema = String::schema();
+        schema.schema_data.title = Some("SmolStr".into());
+        schema
+    }
+}
+
+#[cfg(feature = "serde_bytes")]
+#[test]
+fn parse_example_byte_buf() {
+    parse_example::<serde_bytes::ByteBuf>();
+    parse_example::<crate::types::Base64<serde_bytes::ByteBuf>>();
+
+    let schema = serde_bytes::ByteBuf::schema();
+    assert_eq!(schema.schema_kind, <Vec<u8>>::schema().schema_kind);
+}
+
+/// The JSON has no byte string, so the `serde_json` writes the bytes as the array of integers.
+/// Wrap it with the [`Base64`](crate::types::Base64) to send the base64 encoded string
+/// of the `format: byte` instead.
+#[cfg(feature = "serde_bytes")]
+impl Schema for serde_bytes::ByteBuf {
+    fn schema() -> oa::Schema {
+        let mut schema = <Vec<u8>>::schema();
+        schema.schema_data.title = Some("ByteBuf".into());
+        schema.schema_data.description = Some("Bytes as the array of integers".into());
+        schema.schema_data.example = Some(json!([102, 111, 111]));
+        schema
+    }
+}
+
+/// String schema of the types which are serialized in their `Display` format.
+fn string_schema(
+    title: &str,
+    description: &str,
+    format: oa::VariantOrUnknownOrEmpty<oa::StringFormat>,
+    example: &str,
+) -> oa::Schema {
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: Some(description.into()),
+            example: Some(json!(example)),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+            format,
+            ..Default::default()
+        })),
+    }
+}
+
+#[test]
+fn parse_example_ip_addr() {
+    parse_example::<IpAddr>();
+    parse_example::<Ipv4Addr>();
+    parse_example::<Ipv6Addr>();
+
+    // The std has no zone index, so the scoped addresses are rejected.
+    assert!(serde_json::from_str::<Ipv6Addr>(r#""fe80::1%eth0""#).is_err());
+}
+
+impl Schema for Ipv4Addr {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "Ipv4Addr",
+            "IPv4 address in the dotted decimal notation",
+            oa::VariantOrUnknownOrEmpty::Unknown("ipv4".into()),
+            "192.0.2.1",
+        )
+    }
+}
+
+/// Without the zone index like the `%eth0`, which the `Ipv6Addr` doesn't have.
+impl Schema for Ipv6Addr {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "Ipv6Addr",
+            "IPv6 address without the zone index",
+            oa::VariantOrUnknownOrEmpty::Unknown("ipv6".into()),
+            "2001:db8::1",
+        )
+    }
+}
+
+impl Schema for IpAddr {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "IpAddr",
+            "IPv4 or IPv6 address",
+            oa::VariantOrUnknownOrEmpty::Empty,
+            "192.0.2.1",
+        )
+    }
+}
+
+#[test]
+fn parse_example_system_time() {
+    parse_example::<SystemTime>();
+
+    let example = SystemTime::schema().schema_data.example.unwrap();
+    let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1614834367);
+    assert_eq!(serde_json::to_value(time).unwrap(), example);
+}
+
+/// Follows the serde's representation, which is the object of the seconds
+/// and the subsecond nanoseconds since the UNIX epoch.
+/// Use the `jiff` or `chrono` types for the RFC 3339 string.
+impl Schema for SystemTime {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("SystemTime".into()),
+                description: Some("Time since the UNIX epoch".into()),
+                example: Some(json!({
+                    "secs_since_epoch": 1614834367,
+                    "nanos_since_epoch": 0,
+                })),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+                properties: vec![
+                    ("secs_since_epoch".into(), reference::<u64>()),
+                    ("nanos_since_epoch".into(), reference::<u32>()),
+                ]
+                .into_iter()
+                .collect(),
+                required: vec!["secs_since_epoch".into(), "nanos_since_epoch".into()],
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[cfg(feature = "bstr")]
+#[test]
+fn parse_example_bstring() {
+    parse_example::<bstr::BString>();
+    parse_example::<crate::types::Base64<bstr::BString>>();
+
+    let schema = bstr::BString::schema();
+    assert_eq!(schema.schema_kind, <Vec<u8>>::schema().schema_kind);
+}
+
+/// Like the `serde_bytes::ByteBuf`, the `serde_json` writes the bytes
+/// as the array of integers. Wrap it with the [`Base64`](crate::types::Base64)
+/// to send the base64 encoded string of the `format: byte` instead.
+#[cfg(feature = "bstr")]
+impl Schema for bstr::BString {
+    fn schema() -> oa::Schema {
+        let mut schema = <Vec<u8>>::schema();
+        schema.schema_data.title = Some("BString".into());
+        schema.schema_data.description = Some("Byte string as the array of integers".into());
+        schema.schema_data.example = Some(json!([102, 111, 111]));
+        schema
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+#[test]
+fn parse_example_bigint() {
+    parse_example::<num_bigint::BigInt>();
+    parse_example::<num_bigint::BigUint>();
+}
+
+/// The `num-bigint` serializes it as the `[sign, digits]` array. Wrap it with the
+/// [`AsString`](crate::types::AsString) to send the decimal string instead,
+/// which survives the JSON parsers with the limited number precision.
+#[cfg(feature = "num-bigint")]
+impl Schema for num_bigint::BigInt {
+    fn schema() -> oa::Schema {
+        let example: num_bigint::BigInt = "123456789012345678901234567890".parse().unwrap();
+
+        let mut schema = <(i8, Vec<u32>)>::schema();
+        schema.schema_data.title = Some("BigInt".into());
+        schema.schema_data.description = Some(
+            "Big integer as the sign of -1, 0 or 1 and the base 2^32 digits, \
+            the least significant first"
+                .into(),
+        );
+        schema.schema_data.example = serde_json::to_value(example).ok();
+        schema
+    }
+
+    fn string_pattern() -> Option<&'static str> {
+        Some("^-?[0-9]+$")
+    }
+}
+
+/// The `num-bigint` serializes it as the array of digits. Wrap it with the
+/// [`AsString`](crate::types::AsString) to send the decimal string instead.
+#[cfg(feature = "num-bigint")]
+impl Schema for num_bigint::BigUint {
+    fn schema() -> oa::Schema {
+        let example: num_bigint::BigUint = "123456789012345678901234567890".parse().unwrap();
+
+        let mut schema = <Vec<u32>>::schema();
+        schema.schema_data.title = Some("BigUint".into());
+        schema.schema_data.description = Some(
+            "Big unsigned integer as the base 2^32 digits, the least significant first".into(),
+        );
+        schema.schema_data.example = serde_json::to_value(example).ok();
+        schema
+    }
+
+    fn string_pattern() -> Option<&'static str> {
+        Some("^[0-9]+$")
+    }
+}
+
+#[cfg(feature = "jiff")]
+#[test]
+fn parse_example_jiff() {
+    parse_example::<jiff::Timestamp>();
+    parse_example::<jiff::civil::Date>();
+    parse_example::<jiff::Zoned>();
+}
+
+#[cfg(feature = "jiff")]
+impl Schema for jiff::Timestamp {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "Timestamp",
+            "RFC 3339 timestamp",
+            oa::VariantOrUnknownOrEmpty::Item(oa::StringFormat::DateTime),
+            "2021-03-04T05:06:07Z",
+        )
+    }
+}
+
+#[cfg(feature = "jiff")]
+impl Schema for jiff::civil::Date {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "Date",
+            "RFC 3339 full-date",
+            oa::VariantOrUnknownOrEmpty::Item(oa::StringFormat::Date),
+            "2021-03-04",
+        )
+    }
+}
+
+/// The `Zoned` is serialized as the RFC 3339 date-time followed by the time zone
+/// in brackets, as in the RFC 9557, which isn't the `date-time` format.
+#[cfg(feature = "jiff")]
+impl Schema for jiff::Zoned {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "Zoned",
+            "RFC 3339 date-time with the time zone annotation of the RFC 9557",
+            oa::VariantOrUnknownOrEmpty::Unknown("date-time-zoned".into()),
+            "2021-03-04T05:06:07+09:00[+09:00]",
+        )
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn parse_example_chrono() {
+    parse_example::<chrono::NaiveTime>();
+    parse_example::<chrono::TimeDelta>();
+
+    let delta = chrono::TimeDelta::milliseconds(90_500);
+    let value = serde_json::to_value(delta).unwrap();
+    assert_eq!(
+        value,
+        chrono::TimeDelta::schema().schema_data.example.unwrap()
+    );
+    assert_eq!(
+        serde_json::from_value::<chrono::TimeDelta>(value).unwrap(),
+        delta
+    );
+}
+
+/// The `chrono` serializes the `TimeDelta` as the `[secs, nanos]` array,
+/// where the nanoseconds are always positive. For the ISO 8601 duration string,
+/// use the `types::IsoDuration` of the `iso8601_duration` feature instead.
+#[cfg(feature = "chrono")]
+impl Schema for chrono::TimeDelta {
+    fn schema() -> oa::Schema {
+        let mut schema = <(i64, i32)>::schema();
+        schema.schema_data.title = Some("TimeDelta".into());
+        schema.schema_data.description = Some(
+            "Duration as the [seconds, nanoseconds] array, with the nanoseconds in [0, 1e9)".into(),
+        );
+        schema.schema_data.example = Some(json!([90, 500_000_000]));
+        schema
+    }
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn parse_example_time_duration() {
+    parse_example::<time::Duration>();
+
+    let duration = time::Duration::milliseconds(90_500);
+    let value = serde_json::to_value(duration).unwrap();
+    assert_eq!(value, time::Duration::schema().schema_data.example.unwrap());
+    assert_eq!(
+        serde_json::from_value::<time::Duration>(value).unwrap(),
+        duration
+    );
+}
+
+/// The `time` serializes the `Duration` as the `[secs, nanos]` array,
+/// where both have the sign of the duration. With the `time-human-readable` feature,
+/// which enables the `serde-human-readable` of the `time`, it's the seconds string
+/// with 9 fractional digits like `"90.500000000"` instead.
+#[cfg(feature = "time")]
+impl Schema for time::Duration {
+    fn schema() -> oa::Schema {
+        #[cfg(not(feature = "time-human-readable"))]
+        {
+            let mut schema = <(i64, i32)>::schema();
+            schema.schema_data.title = Some("Duration".into());
+            schema.schema_data.description = Some(
+                "Duration as the [seconds, nanoseconds] array, both with the same sign".into(),
+            );
+            schema.schema_data.example = Some(json!([90, 500_000_000]));
+            schema
+        }
+
+        #[cfg(feature = "time-human-readable")]
+        {
+            oa::Schema {
+                schema_data: oa::SchemaData {
+                    title: Some("Duration".into()),
+                    description: Some("Duration in seconds with 9 fractional digits".into()),
+                    example: Some(json!("90.500000000")),
+                    ..Default::default()
+                },
+                schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                    pattern: Some(r"^-?[0-9]+\.[0-9]{9}$".into()),
+                    ..Default::default()
+                })),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Schema for chrono::NaiveTime {
+    fn schema() -> oa::Schema {
+        string_schema(
+            "NaiveTime",
+            "RFC 3339 partial-time without the time zone",
+            oa::VariantOrUnknownOrEmpty::Unknown("time".into()),
+            "13:30:00",
+        )
+    }
+}
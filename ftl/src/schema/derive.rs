@@ -0,0 +1,1148 @@
+//! Runtime support of the `#[derive(Schema)]`. Not a public API.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use serde_json::{Map, Value};
+
+use super::{reference, reference_unboxed, Schema};
+
+pub use openapiv3 as oa;
+
+/// Property of the derived object schema.
+#[derive(Debug)]
+pub struct Field {
+    name: &'static str,
+    schema: oa::ReferenceOr<Box<oa::Schema>>,
+    example: Option<Value>,
+    required: bool,
+}
+
+impl Field {
+    pub fn new<T: Schema>(
+        name: &'static str,
+        required: bool,
+        description: Option<&'static str>,
+    ) -> Self {
+        let mut schema = reference::<T>();
+        if let (oa::ReferenceOr::Item(schema), Some(description)) = (&mut schema, description) {
+            schema.schema_data.description = Some(description.into());
+        }
+
+        Field {
+            name,
+            schema,
+            example: example::<T>(),
+            required,
+        }
+    }
+
+    /// Shows the `null` as the example of the optional field.
+    pub fn null_example(mut self) -> Self {
+        if let oa::ReferenceOr::Item(schema) = &mut self.schema {
+            schema.schema_data.example = Some(Value::Null);
+        }
+        self.example = Some(Value::Null);
+        self
+    }
+}
+
+thread_local! {
+    /// Named types whose examples are being built, to stop at their self-references.
+    static BUILDING: RefCell<Vec<Cow<'static, str>>> = RefCell::default();
+}
+
+/// Example of the `T`, or `None` for the reference back to the type being built,
+/// like the `Box<Self>` field, which would recurse forever otherwise.
+fn example<T: Schema>() -> Option<Value> {
+    struct Building;
+
+    impl Drop for Building {
+        fn drop(&mut self) {
+            BUILDING.with(|building| building.borrow_mut().pop());
+        }
+    }
+
+    let name = match T::schema_name() {
+        Some(name) => name,
+        None => return T::schema().schema_data.example,
+    };
+    if BUILDING.with(|building| building.borrow().contains(&name)) {
+        return None;
+    }
+
+    BUILDING.with(|building| building.borrow_mut().push(name));
+    let _building = Building;
+    T::schema().schema_data.example
+}
+
+/// Component name of the recursive generic type, like the `Tree_u32` for the `Tree<u32>`.
+pub fn generic_name(base: &str, params: &[String]) -> String {
+    let mut name = base.to_owned();
+    for param in params {
+        name.push('_');
+        name.extend(param.chars().map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        }));
+    }
+    name
+}
+
+/// Name of the type parameter within the [`generic_name`].
+///
+/// Unnamed containers are named after their items too, like the `Vec_u32`
+/// for the `Vec<u32>`, so the different parameters don't share the component.
+pub fn param_name<T: Schema>() -> String {
+    match T::schema_name() {
+        Some(name) => name.into_owned(),
+        None => schema_label(&T::schema()),
+    }
+}
+
+fn schema_label(schema: &oa::Schema) -> String {
+    let title = schema.schema_data.title.as_deref();
+    let label = match &schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Array(array)) => {
+            format!(
+                "{}_{}",
+                title.unwrap_or("Vec"),
+                reference_label(&array.items)
+            )
+        }
+        oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+            additional_properties: Some(oa::AdditionalProperties::Schema(value)),
+            ..
+        })) => match &**value {
+            oa::ReferenceOr::Reference { reference } => {
+                format!("{}_{}", title.unwrap_or("Map"), ref_name(reference))
+            }
+            oa::ReferenceOr::Item(value) => {
+                format!("{}_{}", title.unwrap_or("Map"), schema_label(value))
+            }
+        },
+        oa::SchemaKind::AllOf { all_of } if title.is_none() && all_of.len() == 1 => {
+            match &all_of[0] {
+                oa::ReferenceOr::Reference { reference } => ref_name(reference).into(),
+                oa::ReferenceOr::Item(inner) => schema_label(inner),
+            }
+        }
+        _ => title.unwrap_or("Value").into(),
+    };
+
+    if schema.schema_data.nullable {
+        format!("Option_{}", label)
+    } else {
+        label
+    }
+}
+
+fn reference_label(schema: &oa::ReferenceOr<Box<oa::Schema>>) -> String {
+    match schema {
+        oa::ReferenceOr::Reference { reference } => ref_name(reference).into(),
+        oa::ReferenceOr::Item(schema) => schema_label(schema),
+    }
+}
+
+fn ref_name(reference: &str) -> &str {
+    reference.rsplit('/').next().unwrap_or(reference)
+}
+
+pub fn object(title: &str, description: Option<&str>, fields: Vec<Field>) -> oa::Schema {
+    let mut example = Map::new();
+    let mut object = oa::ObjectType::default();
+
+    for field in fields {
+        if let Some(value) = field.example {
+            example.insert(field.name.into(), value);
+        }
+        if field.required {
+            object.required.push(field.name.into());
+        }
+        object.properties.insert(field.name.into(), field.schema);
+    }
+
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: description.map(Into::into),
+            example: Some(example.into()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::Object(object)),
+    }
+}
+
+/// Object with the known `fields` and the other keys of the `T` values.
+pub fn object_with_additional<T: Schema>(
+    title: &str,
+    description: Option<&str>,
+    fields: Vec<Field>,
+) -> oa::Schema {
+    let mut schema = object(title, description, fields);
+    if let oa::SchemaKind::Type(oa::Type::Object(object)) = &mut schema.schema_kind {
+        object.additional_properties = Some(oa::AdditionalProperties::Schema(Box::new(
+            reference_unboxed::<T>(),
+        )));
+    }
+    schema
+}
+
+pub fn string_enum(title: &str, description: Option<&str>, variants: &[&str]) -> oa::Schema {
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: description.map(Into::into),
+            example: variants.first().map(|&name| name.into()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+            enumeration: variants.iter().map(|&name| name.into()).collect(),
+            ..Default::default()
+        })),
+    }
+}
+
+/// Schema of the enum with the `#[serde(other)]` variant or the `#[non_exhaustive]`,
+/// which accepts any string.
+///
+/// Known variants are listed in the description instead of the `enum`.
+pub fn open_string_enum(title: &str, description: Option<&str>, known: &[&str]) -> oa::Schema {
+    let known_values = format!("Known values: {}", known.join(", "));
+
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: Some(match description {
+                Some(description) => format!("{}\n\n{}", description, known_values),
+                None => known_values,
+            }),
+            example: known.first().map(|&name| name.into()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::String(Default::default())),
+    }
+}
+
+/// Schema of the internally tagged enum.
+///
+/// Each variant becomes the object with the `tag` property of its name,
+/// and the `tag` is set as the discriminator. The `mapping` is left empty
+/// since the variants are inlined and have nothing to refer to.
+pub fn tagged_union(
+    title: &str,
+    description: Option<&str>,
+    tag: &'static str,
+    variants: Vec<(&'static str, Option<&str>, Vec<Field>)>,
+) -> oa::Schema {
+    let mut example = None;
+    let one_of = variants
+        .into_iter()
+        .map(|(name, variant_description, mut fields)| {
+            fields.insert(
+                0,
+                Field {
+                    name: tag,
+                    schema: oa::ReferenceOr::Item(Box::new(string_enum(name, None, &[name]))),
+                    example: Some(name.into()),
+                    required: true,
+                },
+            );
+            let arm = object(name, variant_description, fields);
+            example = example.take().or_else(|| arm.schema_data.example.clone());
+            oa::ReferenceOr::Item(arm)
+        })
+        .collect();
+
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: description.map(Into::into),
+            example,
+            discriminator: Some(oa::Discriminator {
+                property_name: tag.into(),
+                mapping: Default::default(),
+            }),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::OneOf { one_of },
+    }
+}
+
+/// Schema of the integer enum, whose variant names are listed in the description
+/// since the `enum` only holds the discriminants.
+pub fn integer_enum(
+    title: &str,
+    description: Option<&str>,
+    variants: &[(&str, i64)],
+) -> oa::Schema {
+    let mut schema = open_integer_enum(title, description, variants);
+    if let oa::SchemaKind::Type(oa::Type::Integer(int)) = &mut schema.schema_kind {
+        int.enumeration = variants.iter().map(|&(_, value)| value).collect();
+    }
+    schema
+}
+
+/// Unit variant of the externally tagged enum, which is serialized as its name.
+pub fn unit_variant(name: &str, description: Option<&str>) -> oa::Schema {
+    string_enum(name, description, &[name])
+}
+
+/// Newtype variant of the externally tagged enum, `{ "Name": value }`,
+/// where the `value` is the field named after the variant.
+pub fn newtype_variant(description: Option<&str>, value: Field) -> oa::Schema {
+    object(value.name, description, vec![value])
+}
+
+/// Struct variant of the externally tagged enum, `{ "Name": { ...fields } }`.
+pub fn struct_variant(
+    name: &'static str,
+    description: Option<&str>,
+    fields: Vec<Field>,
+) -> oa::Schema {
+    let inner = object(name, None, fields);
+    let value = Field {
+        name,
+        example: inner.schema_data.example.clone(),
+        schema: oa::ReferenceOr::Item(Box::new(inner)),
+        required: true,
+    };
+
+    object(name, description, vec![value])
+}
+
+/// Schema of the externally tagged enum with the data, the `oneOf` of its variants.
+pub fn external_union(title: &str, description: Option<&str>, arms: Vec<oa::Schema>) -> oa::Schema {
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: description.map(Into::into),
+            example: arms.first().and_then(|arm| arm.schema_data.example.clone()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::OneOf {
+            one_of: arms.into_iter().map(oa::ReferenceOr::Item).collect(),
+        },
+    }
+}
+
+/// Schema of the externally tagged enum with the `#[serde(other)]` variant
+/// or the `#[non_exhaustive]`, which also accepts the variants added later.
+pub fn open_external_union(
+    title: &str,
+    description: Option<&str>,
+    arms: Vec<oa::Schema>,
+) -> oa::Schema {
+    let fallback = oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some("Unknown".into()),
+            description: Some("Variant added later".into()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Any(Default::default()),
+    };
+
+    open_union(external_union(title, description, arms), fallback)
+}
+
+/// Schema of the `#[non_exhaustive]` internally tagged enum, which also accepts
+/// the objects with unknown tags for the variants added later.
+pub fn open_tagged_union(
+    title: &str,
+    description: Option<&str>,
+    tag: &'static str,
+    variants: Vec<(&'static str, Option<&str>, Vec<Field>)>,
+) -> oa::Schema {
+    let fallback = object(
+        "Unknown",
+        Some("Variant added later"),
+        vec![Field::new::<String>(tag, true, None)],
+    );
+
+    open_union(tagged_union(title, description, tag, variants), fallback)
+}
+
+/// Schema of the `#[non_exhaustive]` integer enum, which accepts any integer
+/// for the variants added later.
+pub fn open_integer_enum(
+    title: &str,
+    description: Option<&str>,
+    variants: &[(&str, i64)],
+) -> oa::Schema {
+    let known: Vec<_> = variants
+        .iter()
+        .map(|(name, value)| format!("{} = {}", name, value))
+        .collect();
+    let known_values = format!("Known values: {}", known.join(", "));
+
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            description: Some(match description {
+                Some(description) => format!("{}\n\n{}", description, known_values),
+                None => known_values,
+            }),
+            example: variants.first().map(|&(_, value)| value.into()),
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::Integer(oa::IntegerType {
+            format: oa::VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int64),
+            ..Default::default()
+        })),
+    }
+}
+
+/// Turns the `oneOf` into the `anyOf` with the `fallback` arm, since the fallback
+/// matches the known arms too.
+///
+/// The discriminator is dropped as the unknown tags map to no arm.
+fn open_union(mut schema: oa::Schema, mut fallback: oa::Schema) -> oa::Schema {
+    if let oa::SchemaKind::OneOf { one_of } = schema.schema_kind {
+        schema.schema_data.discriminator = None;
+        fallback.schema_data.example = None;
+        let mut any_of = one_of;
+        any_of.push(oa::ReferenceOr::Item(fallback));
+        schema.schema_kind = oa::SchemaKind::AnyOf { any_of };
+    }
+
+    schema
+}
+
+#[cfg(test)]
+mod fixture {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+    use serde_repr::{Deserialize_repr, Serialize_repr};
+
+    use crate::Schema;
+
+    /// Item in the cart.
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Item {
+        /// Number of the items.
+        pub count: u32,
+        pub note: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Filter {
+        pub id: u32,
+        pub tags: Option<Vec<u32>>,
+        pub counts: std::option::Option<HashMap<String, u32>>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    pub enum Status {
+        Active,
+        Inactive,
+        #[serde(other)]
+        Other,
+    }
+
+    /// Shape to draw.
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[serde(tag = "kind", deny_unknown_fields)]
+    pub enum Shape {
+        Circle { radius: f64 },
+        Rect { width: f64, height: f64 },
+        Empty,
+    }
+
+    /// Counts by the label, along with the known fields.
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[schema(additional = u32)]
+    pub struct Counters {
+        pub total: u64,
+        pub note: Option<String>,
+        #[serde(flatten)]
+        pub others: HashMap<String, u32>,
+    }
+
+    /// Edit of the document.
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    pub enum Edit {
+        /// Text inserted at the offset.
+        Insert {
+            offset: u32,
+            text: String,
+        },
+        Delete {
+            offset: u32,
+            length: Option<u32>,
+        },
+        Rename(String),
+        Clear,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize_repr, Deserialize_repr, Schema)]
+    #[repr(i8)]
+    #[schema(repr = "int")]
+    pub enum Priority {
+        Low = -1,
+        Normal,
+        High = 10,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Schema)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Region {
+        UsEast,
+        ApNortheast,
+        #[serde(rename = "eu")]
+        Europe,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Server {
+        pub host_name: String,
+        pub region: Region,
+        #[serde(rename = "TTL")]
+        pub time_to_live: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Profile {
+        pub name: String,
+        #[schema(null_example)]
+        pub nickname: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Counter {
+        pub name: String,
+        #[serde(default)]
+        pub count: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[non_exhaustive]
+    pub enum Color {
+        Red,
+        Green,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[serde(tag = "type")]
+    #[non_exhaustive]
+    pub enum Event {
+        Created { id: u32 },
+        Deleted { id: u32 },
+    }
+
+    /// Node of the tree.
+    /// Expression which refers to itself without the `Option`.
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    pub enum Expr {
+        Lit(i64),
+        Neg(Box<Self>),
+        Add { left: Box<Self>, right: Box<Self> },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Node {
+        pub value: u32,
+        pub children: Vec<Node>,
+        pub parent: Option<Box<Self>>,
+    }
+
+    /// Item of the singly linked list, which refers to itself only through the `next`.
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    pub struct Link {
+        pub value: u32,
+        /// Rest of the list.
+        pub next: Option<Box<Self>>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Page<T> {
+        pub items: Vec<T>,
+        pub next: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Schema)]
+    pub struct Tree<T> {
+        pub value: T,
+        pub children: Vec<Tree<T>>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[serde(transparent)]
+    #[schema(transparent)]
+    pub struct UserId(pub u64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Schema)]
+    #[serde(transparent)]
+    #[schema(transparent)]
+    pub struct Label {
+        pub text: String,
+    }
+}
+
+#[test]
+fn derive_object_schema() {
+    use fixture::Item;
+
+    super::parse_example::<Item>();
+
+    let schema = Item::schema();
+    assert_eq!(
+        schema.schema_data.description.as_deref(),
+        Some("Item in the cart.")
+    );
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            assert_eq!(object.required, ["count"]);
+            match &object.properties["count"] {
+                oa::ReferenceOr::Item(count) => assert_eq!(
+                    count.schema_data.description.as_deref(),
+                    Some("Number of the items.")
+                ),
+                other => panic!("unexpected property {:?}", other),
+            }
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn derive_optional_containers() {
+    use fixture::Filter;
+
+    super::parse_example::<Filter>();
+
+    let object = match Filter::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => object,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    assert_eq!(object.required, ["id"]);
+
+    match &object.properties["tags"] {
+        oa::ReferenceOr::Item(tags) => {
+            assert!(tags.schema_data.nullable);
+            assert!(matches!(
+                tags.schema_kind,
+                oa::SchemaKind::Type(oa::Type::Array(_))
+            ));
+        }
+        other => panic!("unexpected property {:?}", other),
+    }
+    match &object.properties["counts"] {
+        oa::ReferenceOr::Item(counts) => {
+            assert!(counts.schema_data.nullable);
+            assert!(matches!(
+                counts.schema_kind,
+                oa::SchemaKind::Type(oa::Type::Object(_))
+            ));
+        }
+        other => panic!("unexpected property {:?}", other),
+    }
+
+    let filter: Filter = serde_json::from_str(r#"{"id":1}"#).unwrap();
+    assert!(filter.tags.is_none() && filter.counts.is_none());
+}
+
+#[test]
+fn derive_open_enum() {
+    use fixture::Status;
+
+    super::parse_example::<Status>();
+
+    let schema = Status::schema();
+    assert_eq!(
+        schema.schema_data.description.as_deref(),
+        Some("Known values: Active, Inactive")
+    );
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => assert!(string.enumeration.is_empty()),
+        other => panic!("unexpected schema {:?}", other),
+    }
+
+    assert_eq!(
+        serde_json::from_str::<Status>(r#""Suspended""#).unwrap(),
+        Status::Other
+    );
+}
+
+#[test]
+fn derive_tagged_enum() {
+    use fixture::Shape;
+
+    super::parse_example::<Shape>();
+
+    let schema = Shape::schema();
+    assert_eq!(
+        schema.schema_data.discriminator.unwrap().property_name,
+        "kind"
+    );
+
+    let arms = match schema.schema_kind {
+        oa::SchemaKind::OneOf { one_of } => one_of,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    assert_eq!(arms.len(), 3);
+    match &arms[1] {
+        oa::ReferenceOr::Item(oa::Schema {
+            schema_data,
+            schema_kind: oa::SchemaKind::Type(oa::Type::Object(object)),
+        }) => {
+            assert_eq!(object.required, ["kind", "width", "height"]);
+            let example = schema_data.example.clone().unwrap();
+            let rect: Shape = serde_json::from_value(example).unwrap();
+            assert_eq!(
+                rect,
+                Shape::Rect {
+                    width: 1.0,
+                    height: 1.0
+                }
+            );
+        }
+        other => panic!("unexpected arm {:?}", other),
+    }
+}
+
+#[test]
+fn derive_externally_tagged_enum() {
+    use fixture::Edit;
+
+    super::parse_example::<Edit>();
+
+    let arms = match Edit::schema().schema_kind {
+        oa::SchemaKind::OneOf { one_of } => one_of,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    let arms: Vec<_> = arms
+        .into_iter()
+        .map(|arm| match arm {
+            oa::ReferenceOr::Item(arm) => arm,
+            other => panic!("unexpected arm {:?}", other),
+        })
+        .collect();
+    assert_eq!(arms.len(), 4);
+    assert_eq!(arms[3], unit_variant("Clear", None));
+
+    // Checks the serialized value against the arm, as far as the objects go.
+    fn matches(schema: &oa::Schema, value: &Value) -> bool {
+        match (&schema.schema_kind, value) {
+            (oa::SchemaKind::Type(oa::Type::Object(object)), Value::Object(map)) => {
+                object.required.iter().all(|name| map.contains_key(name))
+                    && map
+                        .iter()
+                        .all(|(name, value)| match object.properties.get(name) {
+                            Some(oa::ReferenceOr::Item(schema)) => matches(schema, value),
+                            Some(oa::ReferenceOr::Reference { .. }) => true,
+                            None => false,
+                        })
+            }
+            (oa::SchemaKind::Type(oa::Type::Object(_)), _) | (_, Value::Object(_)) => false,
+            (oa::SchemaKind::Type(oa::Type::String(string)), Value::String(value)) => {
+                string.enumeration.is_empty() || string.enumeration.contains(value)
+            }
+            _ => true,
+        }
+    }
+
+    let events = [
+        Edit::Insert {
+            offset: 3,
+            text: "foo".into(),
+        },
+        Edit::Delete {
+            offset: 3,
+            length: None,
+        },
+        Edit::Rename("bar".into()),
+        Edit::Clear,
+    ];
+    for (index, event) in events.iter().enumerate() {
+        let value = serde_json::to_value(event).unwrap();
+        let matched: Vec<_> = (0..arms.len())
+            .filter(|&arm| matches(&arms[arm], &value))
+            .collect();
+        assert_eq!(matched, [index], "{} matches the arms {:?}", value, matched);
+    }
+
+    match &arms[0].schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            assert_eq!(object.required, ["Insert"]);
+            assert_eq!(object.properties.len(), 1);
+        }
+        other => panic!("unexpected arm {:?}", other),
+    }
+    assert_eq!(
+        arms[0].schema_data.description.as_deref(),
+        Some("Text inserted at the offset.")
+    );
+    assert_eq!(
+        serde_json::from_value::<Edit>(arms[1].schema_data.example.clone().unwrap()).unwrap(),
+        Edit::Delete {
+            offset: 1,
+            length: Some(1)
+        }
+    );
+}
+
+#[test]
+fn derive_additional_properties() {
+    use fixture::Counters;
+
+    super::parse_example::<Counters>();
+
+    let object = match Counters::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => object,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    assert_eq!(object.required, ["total"]);
+    assert_eq!(
+        object.properties.keys().collect::<Vec<_>>(),
+        ["total", "note"]
+    );
+    assert_eq!(
+        object.additional_properties,
+        Some(oa::AdditionalProperties::Schema(Box::new(
+            reference_unboxed::<u32>()
+        )))
+    );
+
+    let counters: Counters = serde_json::from_str(r#"{"total":3,"a":1,"b":2}"#).unwrap();
+    assert_eq!(counters.total, 3);
+    assert_eq!(counters.others.len(), 2);
+}
+
+#[test]
+fn derive_integer_enum() {
+    use fixture::Priority;
+
+    super::parse_example::<Priority>();
+
+    let schema = Priority::schema();
+    assert!(schema
+        .schema_data
+        .description
+        .unwrap()
+        .contains("High = 10"));
+    let values = match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Integer(int)) => int.enumeration,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    assert_eq!(values, [-1, 0, 10]);
+
+    assert_eq!(serde_json::to_string(&Priority::High).unwrap(), "10");
+    assert_eq!(
+        serde_json::from_str::<Priority>("0").unwrap(),
+        Priority::Normal
+    );
+    assert!(serde_json::from_str::<Priority>("3").is_err());
+}
+
+#[test]
+fn derive_transparent_newtype() {
+    use fixture::{Label, UserId};
+
+    super::parse_example::<UserId>();
+    super::parse_example::<Label>();
+
+    let json = |schema: oa::Schema| serde_json::to_value(schema).unwrap();
+    assert_eq!(json(UserId::schema()), json(u64::schema()));
+    assert_eq!(json(Label::schema()), json(String::schema()));
+    assert_eq!(UserId::schema_name(), u64::schema_name());
+
+    assert_eq!(serde_json::to_string(&UserId(42)).unwrap(), "42");
+}
+
+#[test]
+fn derive_renamed() {
+    use fixture::{Region, Server};
+
+    super::parse_example::<Region>();
+    super::parse_example::<Server>();
+
+    match Region::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => {
+            assert_eq!(string.enumeration, ["us-east", "ap-northeast", "eu"])
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+    assert_eq!(
+        serde_json::from_str::<Region>(r#""ap-northeast""#).unwrap(),
+        Region::ApNortheast
+    );
+    assert_eq!(serde_json::to_string(&Region::Europe).unwrap(), r#""eu""#);
+
+    match Server::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            let names: Vec<_> = object.properties.keys().map(String::as_str).collect();
+            assert_eq!(names, ["hostName", "region", "TTL"]);
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn derive_recursive() {
+    use fixture::Node;
+
+    super::parse_example::<Node>();
+    assert_eq!(Node::schema_name().as_deref(), Some("Node"));
+
+    let node_ref = oa::ReferenceOr::ref_("#/components/schemas/Node");
+    let object = match Node::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => object,
+        other => panic!("unexpected schema {:?}", other),
+    };
+    match &object.properties["children"] {
+        oa::ReferenceOr::Item(children) => match &children.schema_kind {
+            oa::SchemaKind::Type(oa::Type::Array(array)) => assert_eq!(array.items, node_ref),
+            other => panic!("unexpected children {:?}", other),
+        },
+        other => panic!("unexpected children {:?}", other),
+    }
+    match &object.properties["parent"] {
+        oa::ReferenceOr::Item(parent) => {
+            assert!(parent.schema_data.nullable);
+            assert!(
+                matches!(&parent.schema_kind, oa::SchemaKind::AllOf { all_of } if all_of.len() == 1)
+            );
+        }
+        other => panic!("unexpected parent {:?}", other),
+    }
+
+    let mut registry = super::SchemaRegistry::new();
+    registry.register::<Node>().register::<Vec<Node>>();
+    assert_eq!(registry.len(), 1);
+    assert!(registry.get("Node").is_some());
+}
+
+#[test]
+fn derive_optional_boxed_self() {
+    use fixture::Link;
+
+    super::parse_example::<Link>();
+    assert_eq!(Link::schema_name().as_deref(), Some("Link"));
+
+    // The `$ref` can't have the `nullable` next to it, so it's wrapped with the `allOf`.
+    let schema = serde_json::to_value(Link::schema()).unwrap();
+    assert_eq!(
+        schema["properties"]["next"],
+        serde_json::json!({
+            "description": "Rest of the list.",
+            "nullable": true,
+            "allOf": [{ "$ref": "#/components/schemas/Link" }],
+        })
+    );
+    assert_eq!(schema["required"], serde_json::json!(["value"]));
+
+    let mut registry = super::SchemaRegistry::new();
+    registry
+        .register::<Link>()
+        .register::<Option<Box<Link>>>()
+        .register::<Box<Option<Link>>>();
+    assert_eq!(registry.len(), 1);
+
+    let list = Link {
+        value: 1,
+        next: Some(Box::new(Link {
+            value: 2,
+            next: None,
+        })),
+    };
+    let value = serde_json::to_value(&list).unwrap();
+    assert_eq!(value["next"]["next"], Value::Null);
+    assert_eq!(serde_json::from_value::<Link>(value).unwrap(), list);
+}
+
+#[test]
+fn derive_boxed_self() {
+    use fixture::Expr;
+
+    super::parse_example::<Expr>();
+    assert_eq!(Expr::schema_name().as_deref(), Some("Expr"));
+
+    let schema = serde_json::to_value(Expr::schema()).unwrap();
+    let expr_ref = serde_json::json!({ "$ref": "#/components/schemas/Expr" });
+    assert_eq!(schema["oneOf"][1]["properties"]["Neg"], expr_ref);
+    assert_eq!(
+        schema["oneOf"][2]["properties"]["Add"]["properties"]["left"],
+        expr_ref
+    );
+
+    // The examples nest the self-references once.
+    for arm in 1..3 {
+        let example = schema["oneOf"][arm]["example"].clone();
+        serde_json::from_value::<Expr>(example).unwrap();
+    }
+
+    let mut registry = super::SchemaRegistry::new();
+    registry.register::<Expr>().register::<Box<Expr>>();
+    assert_eq!(registry.len(), 1);
+}
+
+#[test]
+fn derive_generic() {
+    use fixture::{Item, Node, Page, Tree};
+
+    super::parse_example::<Page<u32>>();
+    super::parse_example::<Page<Item>>();
+    assert_eq!(Page::<u32>::schema_name(), None);
+
+    let items = |schema: oa::Schema| match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            assert_eq!(object.required, ["items"]);
+            match object.properties["items"].clone() {
+                oa::ReferenceOr::Item(items) => match items.schema_kind {
+                    oa::SchemaKind::Type(oa::Type::Array(array)) => array.items,
+                    other => panic!("unexpected items {:?}", other),
+                },
+                other => panic!("unexpected items {:?}", other),
+            }
+        }
+        other => panic!("unexpected schema {:?}", other),
+    };
+    assert_eq!(
+        items(Page::<u32>::schema()),
+        oa::ReferenceOr::Item(Box::new(u32::schema()))
+    );
+    assert_eq!(
+        items(Page::<Item>::schema()),
+        oa::ReferenceOr::Item(Box::new(Item::schema()))
+    );
+
+    super::parse_example::<Tree<u32>>();
+    assert_eq!(Tree::<u32>::schema_name().as_deref(), Some("Tree_u32"));
+    assert_eq!(Tree::<Node>::schema_name().as_deref(), Some("Tree_Node"));
+
+    let mut registry = super::SchemaRegistry::new();
+    registry
+        .register::<Tree<u32>>()
+        .register::<Tree<Node>>()
+        .register::<Tree<Vec<u32>>>()
+        .register::<Tree<Vec<String>>>()
+        .register::<Tree<Vec<Node>>>()
+        .register::<Tree<Option<Node>>>();
+    let names: Vec<_> = registry.components().schemas.into_keys().collect();
+    assert_eq!(
+        names,
+        [
+            "Tree_u32",
+            "Tree_Node",
+            "Node",
+            "Tree_Vec_u32",
+            "Tree_Vec_String",
+            "Tree_Vec_Node",
+            "Tree_Option_Node"
+        ]
+    );
+}
+
+#[test]
+fn derive_null_example() {
+    use fixture::Profile;
+
+    super::parse_example::<Profile>();
+
+    let schema = Profile::schema();
+    assert_eq!(
+        schema.schema_data.example,
+        Some(serde_json::json!({"name": "foobar", "nickname": null}))
+    );
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => match &object.properties["nickname"] {
+            oa::ReferenceOr::Item(nickname) => {
+                assert!(nickname.schema_data.nullable);
+                assert_eq!(nickname.schema_data.example, Some(Value::Null));
+                assert_eq!(nickname.schema_kind, String::schema().schema_kind);
+            }
+            other => panic!("unexpected property {:?}", other),
+        },
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn derive_enum_keyed_map() {
+    use fixture::{Color, Region};
+    use std::collections::HashMap;
+
+    use crate::schema::SchemaKey;
+
+    super::parse_example::<HashMap<Region, u32>>();
+    assert_eq!(
+        Region::keys(),
+        Some(vec!["us-east".into(), "ap-northeast".into(), "eu".into()])
+    );
+    assert_eq!(Color::keys(), None);
+
+    let schema = HashMap::<Region, u32>::schema();
+    assert_eq!(
+        schema.schema_data.example,
+        Some(serde_json::json!({"us-east": 1}))
+    );
+    assert!(schema
+        .schema_data
+        .description
+        .unwrap()
+        .contains("us-east, ap-northeast, eu"));
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => assert_eq!(
+            object.additional_properties,
+            Some(oa::AdditionalProperties::Schema(Box::new(
+                oa::ReferenceOr::Item(u32::schema())
+            )))
+        ),
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn derive_serde_default() {
+    use fixture::Counter;
+
+    super::parse_example::<Counter>();
+
+    let counter: Counter = serde_json::from_str(r#"{"name": "visits"}"#).unwrap();
+    assert_eq!(counter.count, 0);
+
+    match Counter::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            assert_eq!(object.required, ["name"]);
+            match &object.properties["count"] {
+                oa::ReferenceOr::Item(count) => {
+                    assert!(!count.schema_data.nullable);
+                    assert_eq!(count.schema_kind, u32::schema().schema_kind);
+                }
+                other => panic!("unexpected property {:?}", other),
+            }
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn derive_non_exhaustive() {
+    use fixture::{Color, Event};
+
+    super::parse_example::<Color>();
+    super::parse_example::<Event>();
+
+    match Color::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => assert!(string.enumeration.is_empty()),
+        other => panic!("unexpected schema {:?}", other),
+    }
+
+    let schema = Event::schema();
+    assert!(schema.schema_data.discriminator.is_none());
+    match schema.schema_kind {
+        oa::SchemaKind::AnyOf { any_of } => {
+            assert_eq!(any_of.len(), 3);
+            match &any_of[2] {
+                oa::ReferenceOr::Item(oa::Schema {
+                    schema_kind: oa::SchemaKind::Type(oa::Type::Object(fallback)),
+                    ..
+                }) => assert_eq!(fallback.required, ["type"]),
+                other => panic!("unexpected fallback {:?}", other),
+            }
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
@@ -1,24 +1,54 @@
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::convert::TryInto;
+use std::fmt;
+#[cfg(feature = "tokio-runtime")]
+use std::future::Future;
 use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use bytes::BytesMut;
+use futures_core::Stream;
 use futures_util::future::{ready, BoxFuture, Ready};
-use http::header::{self, HeaderMap};
+#[cfg(feature = "tokio-runtime")]
+use futures_util::future::{select, Either, FutureExt};
+use futures_util::stream::{BoxStream, StreamExt};
+use http::header::{self, HeaderMap, HeaderValue};
 use http::request::{self, Request};
 use http::{Response, StatusCode};
 use hyper::body::{Body, Bytes};
+#[cfg(feature = "tokio-runtime")]
+use hyper::server::conn::{AddrIncoming, AddrStream};
 use hyper::service::Service as HyperService;
+#[cfg(feature = "tokio-runtime")]
+use hyper::Server;
 use strum::IntoEnumIterator;
 
-use crate::error::{BaseError, DynError};
+use crate::codec::ExpectJson;
+use crate::error::{BaseError, DynError, LanguageTag};
 use crate::method::SupportedMethod;
-use crate::router::Router;
+use crate::router::{insert_default_headers, insert_router_headers, Router};
+use crate::sse::EventStream;
 use crate::BoxError;
 
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+mod compression;
+#[cfg(feature = "tokio-runtime")]
+mod incoming;
+#[cfg(feature = "rustls")]
+mod tls;
+
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+pub use compression::Algorithm;
+#[cfg(feature = "tokio-runtime")]
+pub use incoming::Conn;
+#[cfg(feature = "tokio-runtime")]
+use incoming::Incoming;
+
 #[derive(Debug)]
 pub struct Service<T, H>
 where
@@ -34,6 +64,9 @@ where
 {
     router: Router<T, H>,
     config: Arc<Config>,
+    remote_addr: Option<SocketAddr>,
+    #[cfg(feature = "tokio-runtime")]
+    limiter: Limiter,
 }
 
 #[derive(Debug, Default)]
@@ -44,15 +77,332 @@ pub struct Builder {
 #[derive(Debug, Default)]
 struct Config {
     max_request_length: Option<usize>,
+    max_uri_length: Option<usize>,
+    streaming_threshold: Option<usize>,
     #[cfg(feature = "tokio-runtime")]
     request_read_timeout: Option<Duration>,
+    #[cfg(feature = "tokio-runtime")]
+    min_body_rate: Option<u64>,
+    #[cfg(all(feature = "http1", feature = "tokio-runtime"))]
+    header_read_timeout: Option<Duration>,
+    #[cfg(feature = "tokio-runtime")]
+    response_write_timeout: Option<Duration>,
+    #[cfg(feature = "rustls")]
+    tls_handshake_timeout: Option<Duration>,
+    trust_proxy: Option<usize>,
+    https_redirect: bool,
+    expect_continue: bool,
+    #[cfg(feature = "http2")]
+    http2_only: bool,
+    validate_responses: bool,
+    max_response_length: Option<(usize, bool)>,
+    json_number_precision: Option<u32>,
+    #[cfg(feature = "tokio-runtime")]
+    shutdown_timeout: Option<Duration>,
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    compression: Vec<(Algorithm, u32)>,
+    path_normalization: PathNormalization,
+    #[cfg(feature = "tokio-runtime")]
+    concurrency_limit: Option<Arc<tokio::sync::Semaphore>>,
+    #[cfg(feature = "tokio-runtime")]
+    max_connections: Option<usize>,
+    per_ip_concurrency: Option<Arc<PerIpLimit>>,
+    error_localizer: Option<ErrorLocalizer>,
+    body_buffer_pool: Option<Arc<BufferPool>>,
+    expect_json: Option<ExpectJson>,
+    default_response_headers: HeaderMap,
+    default_cache_control: Option<HeaderValue>,
+    observers: Observers,
+    /// Replaces the [`DEFAULT_SENSITIVE_HEADERS`] if set.
+    sensitive_headers: Option<Vec<header::HeaderName>>,
+}
+
+/// Headers redacted from the logs unless the [`Builder::with_sensitive_headers`] is set.
+pub const DEFAULT_SENSITIVE_HEADERS: [header::HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::PROXY_AUTHORIZATION,
+    header::COOKIE,
+    header::SET_COOKIE,
+];
+
+/// Time allowed for the TLS handshake unless the [`Builder::tls_handshake_timeout`] is set.
+#[cfg(feature = "rustls")]
+pub const DEFAULT_TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Formats the headers with the values of the sensitive ones replaced with `***`.
+struct RedactedHeaders<'a> {
+    headers: &'a HeaderMap,
+    sensitive: &'a [header::HeaderName],
+}
+
+/// Waits in the `poll_ready` until the concurrency limit has a free permit.
+///
+/// The permit isn't held until the `call` acquires it for the request,
+/// since the hyper polls the readiness of the idle keep-alive connections too.
+#[cfg(feature = "tokio-runtime")]
+#[derive(Default)]
+struct Limiter {
+    acquiring: Option<BoxFuture<'static, tokio::sync::OwnedSemaphorePermit>>,
+}
+
+/// Translates the message of the error into the language, if it can.
+#[allow(clippy::type_complexity)]
+struct ErrorLocalizer(Box<dyn Fn(&BaseError, &LanguageTag) -> Option<String> + Send + Sync>);
+
+/// Callbacks of the [`Builder::on_request`] and the [`Builder::on_response`].
+#[allow(clippy::type_complexity)]
+#[derive(Default)]
+struct Observers {
+    on_request: Option<Box<dyn Fn(&request::Parts) + Send + Sync>>,
+    on_response: Option<Box<dyn Fn(&request::Parts, StatusCode, Duration) + Send + Sync>>,
+}
+
+/// Requests in flight for each [`ClientIp`].
+///
+/// The requests whose IP is unknown share the `None` entry.
+/// Entries are removed once their last request is done,
+/// so the idle clients take no memory.
+#[derive(Debug)]
+struct PerIpLimit {
+    limit: usize,
+    in_flight: Mutex<HashMap<Option<IpAddr>, usize>>,
+}
+
+/// Counts the request as in flight until dropped.
+struct PerIpPermit {
+    limit: Arc<PerIpLimit>,
+    ip: Option<IpAddr>,
+}
+
+/// Request body buffers recycled across the requests,
+/// set by the [`Builder::request_body_buffer_reuse`].
+#[derive(Debug)]
+struct BufferPool {
+    /// Bodies up to this length are read into the pooled buffers.
+    capacity: usize,
+    max_buffers: usize,
+    buffers: Mutex<Vec<BytesMut>>,
+}
+
+/// How the request path is normalized before routing.
+///
+/// Every mode other than the `Off` collapses the duplicate slashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathNormalization {
+    /// Routes the path as is.
+    #[default]
+    Off,
+    /// Rewrites the path without the trailing slash.
+    Strip,
+    /// Rewrites the path with the trailing slash.
+    Append,
+    /// Redirects to the path without the trailing slash with `308 Permanent Redirect`.
+    Redirect,
+}
+
+/// Reason why the server failed to run.
+///
+/// Converts into the boxed error like any other error,
+/// so the `?` keeps working in functions returning `Box<dyn Error>`.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RunError {
+    /// Failed to listen on the address, e.g. it's already in use.
+    #[error("failed to bind to {addr}")]
+    Bind {
+        addr: SocketAddr,
+        #[source]
+        source: hyper::Error,
+    },
+    /// The server stopped with the error while serving.
+    #[error("server error")]
+    Serve(#[source] hyper::Error),
+    /// Remaining connections have been aborted after the [`Builder::shutdown_timeout`].
+    #[error("connections were aborted after the shutdown timeout")]
+    ShutdownTimeout,
+}
+
+impl Config {
+    fn sensitive_headers(&self) -> &[header::HeaderName] {
+        self.sensitive_headers
+            .as_deref()
+            .unwrap_or(&DEFAULT_SENSITIVE_HEADERS)
+    }
+
+    /// Protocols to advertise with the ALPN, in the order of preference.
+    #[cfg(feature = "rustls")]
+    fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        #[allow(unused_mut)]
+        let mut protocols = vec![];
+        #[cfg(feature = "http2")]
+        protocols.push(b"h2".to_vec());
+        #[cfg(all(feature = "http1", feature = "http2"))]
+        let http1 = !self.http2_only;
+        #[cfg(all(feature = "http1", not(feature = "http2")))]
+        let http1 = true;
+        #[cfg(feature = "http1")]
+        if http1 {
+            protocols.push(b"http/1.1".to_vec());
+        }
+        protocols
+    }
+}
+
+impl fmt::Debug for ErrorLocalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ErrorLocalizer")
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("on_request", &self.on_request.is_some())
+            .field("on_response", &self.on_response.is_some())
+            .finish()
+    }
+}
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+
+        for (name, value) in self.headers {
+            if self.sensitive.contains(name) {
+                map.entry(name, &"***");
+            } else {
+                map.entry(name, value);
+            }
+        }
+
+        map.finish()
+    }
+}
+
+impl PerIpLimit {
+    fn try_acquire(self: &Arc<Self>, ip: Option<IpAddr>) -> Option<PerIpPermit> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(ip).or_insert(0);
+        if *count >= self.limit {
+            return None;
+        }
+
+        *count += 1;
+        Some(PerIpPermit {
+            limit: Arc::clone(self),
+            ip,
+        })
+    }
+}
+
+impl Drop for PerIpPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.limit.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.ip);
+            }
+        }
+    }
+}
+
+impl BufferPool {
+    /// Returns the empty buffer of the `capacity`, or `None` if the body doesn't fit.
+    fn take(&self, content_length: usize) -> Option<BytesMut> {
+        if content_length > self.capacity {
+            return None;
+        }
+
+        let pooled = self.buffers.lock().unwrap().pop();
+        Some(pooled.unwrap_or_else(|| BytesMut::with_capacity(self.capacity)))
+    }
+
+    /// Keeps the buffer for the later requests, if no one else refers to it
+    /// and it's still within the size class.
+    fn put(&self, buf: Bytes) {
+        let mut buf = match buf.try_into_mut() {
+            Ok(buf) => buf,
+            Err(_) => return,
+        };
+        if buf.capacity() < self.capacity || buf.capacity() > self.capacity * 2 {
+            return;
+        }
+
+        buf.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+}
+
+impl PathNormalization {
+    /// Returns `None` if the path is already normalized.
+    fn normalize(self, path: &str) -> Option<String> {
+        if self == PathNormalization::Off {
+            return None;
+        }
+
+        let segments: Vec<&str> = path.split('/').filter(|seg| !seg.is_empty()).collect();
+        let mut normalized = format!("/{}", segments.join("/"));
+        if self == PathNormalization::Append && !segments.is_empty() {
+            normalized.push('/');
+        }
+
+        if normalized == path {
+            None
+        } else {
+            Some(normalized)
+        }
+    }
+}
+
+/// IP address of the client, inserted into the request extensions.
+///
+/// It's the address of the peer, or the one resolved from the `X-Forwarded-For`
+/// header if [`Builder::trust_proxy`] is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientIp(pub IpAddr);
+
+/// Unbuffered request body, inserted into the request extensions
+/// when its length exceeds the [`Builder::request_body_streaming_threshold`].
+///
+/// The body passed to the handler is empty in this case.
+/// Take this out of the extensions to consume the body as a stream.
+#[derive(Debug)]
+pub struct StreamingBody(pub Body);
+
+/// Connection types which know the address of the peer.
+pub trait RemoteAddr {
+    fn remote_addr(&self) -> Option<SocketAddr>;
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct OutBuffer {
-    inner: Option<String>,
+    inner: Option<Vec<u8>>,
+    chunks: Option<Chunks>,
+    trailers: Option<HeaderMap>,
 }
 
+/// Chunks streamed after the buffer. Clones share the same stream.
+#[derive(Clone)]
+struct Chunks(Arc<Mutex<BoxStream<'static, Vec<u8>>>>);
+
+impl fmt::Debug for Chunks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chunks").finish_non_exhaustive()
+    }
+}
+
+/// Trailers sent after the response body.
+///
+/// Insert this into the extensions of the response to send them.
+/// They're only sent over the HTTP/2, since the hyper doesn't write
+/// the trailers of the HTTP/1.1 chunked encoding.
+#[derive(Debug, Clone, Default)]
+pub struct Trailers(pub HeaderMap);
+
 impl<T, H> Service<T, H>
 where
     T: Send + Sync + 'static + ?Sized,
@@ -76,215 +426,2761 @@ where
     pub fn app(&self) -> Arc<T> {
         Arc::clone(&self.router.app)
     }
-}
 
-impl Builder {
-    pub fn new() -> Self {
-        Default::default()
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn run(self, addr: SocketAddr) -> Result<(), RunError> {
+        self.bind(addr)?
+            .0
+            .serve(self)
+            .await
+            .map_err(RunError::Serve)
     }
 
-    pub fn max_reqeust_length(mut self, length: usize) -> Self {
-        self.config.max_request_length = Some(length);
-        self
+    /// Runs the server like the [`run`](Service::run), sending the bound address
+    /// to the `ready` once the listener accepts the connections.
+    ///
+    /// Connections made after the address is received are served without retries,
+    /// so the tests and the orchestrators don't need to poll or sleep.
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn run_with_ready(
+        self,
+        addr: SocketAddr,
+        ready: tokio::sync::oneshot::Sender<SocketAddr>,
+    ) -> Result<(), RunError> {
+        let (server, local_addr) = self.bind(addr)?;
+        // The receiver may not care anymore, which doesn't stop the server.
+        let _ = ready.send(local_addr);
+
+        server.serve(self).await.map_err(RunError::Serve)
     }
 
+    /// Binds to the `addr` and spawns the server onto the current runtime.
+    ///
+    /// Returns the handle of the server task along with the bound address,
+    /// which is useful to bind to the port `0`. Abort the handle to stop the server.
     #[cfg(feature = "tokio-runtime")]
-    pub fn request_read_timeout(mut self, timeout: Duration) -> Self {
-        self.config.request_read_timeout = Some(timeout);
-        self
+    pub fn spawn(
+        self,
+        addr: SocketAddr,
+    ) -> Result<(tokio::task::JoinHandle<Result<(), RunError>>, SocketAddr), RunError> {
+        let (server, local_addr) = self.bind(addr)?;
+        let handle = tokio::spawn(async move { server.serve(self).await.map_err(RunError::Serve) });
+
+        Ok((handle, local_addr))
     }
 
-    pub fn build<T, H>(self, router: Router<T, H>) -> Service<T, H>
+    /// Runs the server until the `signal` resolves, then stops accepting connections
+    /// and waits for the in-flight requests to complete.
+    ///
+    /// If the [`Builder::shutdown_timeout`] is set, remaining connections are aborted
+    /// once it elapses after the signal, and the [`RunError::ShutdownTimeout`] is returned.
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn run_with_shutdown<F>(self, addr: SocketAddr, signal: F) -> Result<(), RunError>
     where
-        T: Send + Sync + 'static + ?Sized,
-        H: for<'a> Fn(
-                Arc<T>,
-                Request<Result<&'a str, Box<BaseError>>>,
-            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
-            + Clone
-            + Send
-            + Sync
-            + 'static,
+        F: Future<Output = ()> + Send + 'static,
     {
-        Service {
-            router,
-            config: Arc::new(self.config),
-        }
+        let (server, _) = self.bind(addr)?;
+        self.serve_with_shutdown(server, signal).await
     }
-}
 
-impl<'c, C, T, H> HyperService<&'c C> for Service<T, H>
-where
-    T: Send + Sync + 'static + ?Sized,
-    H: for<'a> Fn(
-            Arc<T>,
-            Request<Result<&'a str, Box<BaseError>>>,
-        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
-        + Clone
-        + Send
-        + Sync
-        + 'static,
-{
-    type Response = Self;
-    type Error = Infallible;
-    type Future = Ready<Result<Self, Infallible>>;
+    #[cfg(feature = "tokio-runtime")]
+    async fn serve_with_shutdown<F>(
+        self,
+        server: hyper::server::Builder<Incoming>,
+        signal: F,
+    ) -> Result<(), RunError>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let executor = ConnExecutor::default();
+        let shutdown_timeout = self.config.shutdown_timeout;
+        let signal = signal.boxed().shared();
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+        let server = server
+            .executor(executor.clone())
+            .serve(self)
+            .with_graceful_shutdown(signal.clone());
+
+        let timeout = match shutdown_timeout {
+            Some(timeout) => signal.then(move |()| tokio::time::sleep(timeout)).boxed(),
+            None => futures_util::future::pending().boxed(),
+        };
+
+        match select(server, timeout).await {
+            Either::Left((res, _)) => res.map_err(RunError::Serve),
+            Either::Right(((), _)) => {
+                executor.abort_all();
+                Err(RunError::ShutdownTimeout)
+            }
+        }
     }
 
-    fn call(&mut self, _req: &'c C) -> Self::Future {
-        ready(Ok(self.clone()))
+    /// Runs the server with the `layer` applied to the service of each connection.
+    ///
+    /// The `Service` implements the `tower::Service`, as the hyper's `Service` is the same trait.
+    /// So it can also be wrapped with the tower layers directly and driven without the server.
+    #[cfg(all(feature = "tower", feature = "tokio-runtime"))]
+    pub async fn run_with_layer<L, B>(self, addr: SocketAddr, layer: L) -> Result<(), RunError>
+    where
+        L: tower::Layer<Self> + Send + 'static,
+        L::Service: HyperService<Request<Body>, Response = Response<B>> + Send + 'static,
+        <L::Service as HyperService<Request<Body>>>::Error: Into<BoxError>,
+        <L::Service as HyperService<Request<Body>>>::Future: Send + 'static,
+        B: hyper::body::HttpBody + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<BoxError>,
+    {
+        let (server, _) = self.bind(addr)?;
+        self.serve_with_layer(server, layer).await
     }
-}
 
-impl<T, H> HyperService<Request<Body>> for Service<T, H>
-where
-    T: Send + Sync + 'static + ?Sized,
-    H: for<'a> Fn(
-            Arc<T>,
-            Request<Result<&'a str, Box<BaseError>>>,
-        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
-        + Clone
-        + Send
-        + Sync
-        + 'static,
-{
-    type Response = Response<OutBuffer>;
-    type Error = BoxError;
-    // TODO: apply existential type when available
-    type Future = BoxFuture<'static, Result<Response<OutBuffer>, BoxError>>;
+    #[cfg(all(feature = "tower", feature = "tokio-runtime"))]
+    async fn serve_with_layer<L, B>(
+        self,
+        server: hyper::server::Builder<Incoming>,
+        layer: L,
+    ) -> Result<(), RunError>
+    where
+        L: tower::Layer<Self> + Send + 'static,
+        L::Service: HyperService<Request<Body>, Response = Response<B>> + Send + 'static,
+        <L::Service as HyperService<Request<Body>>>::Error: Into<BoxError>,
+        <L::Service as HyperService<Request<Body>>>::Future: Send + 'static,
+        B: hyper::body::HttpBody + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<BoxError>,
+    {
+        let make_service = hyper::service::make_service_fn(move |conn: &Conn| {
+            let mut service = self.clone();
+            service.remote_addr = Some(conn.remote_addr());
+            ready(Ok::<_, Infallible>(layer.layer(service)))
+        });
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+        server.serve(make_service).await.map_err(RunError::Serve)
     }
 
-    fn call(&mut self, req: Request<Body>) -> Self::Future {
-        let router = self.router.clone();
-        let config = Arc::clone(&self.config);
-
-        Box::pin(async move {
-            let (parts, body) = req.into_parts();
-            let mut buf = Bytes::new();
-            let body = parse_request(&parts, body, Arc::clone(&config), &mut buf).await;
-            let resp = (router.handler)(router.app, Request::from_parts(parts, body)).await?;
-            Ok(resp.map(From::from))
-        })
+    /// Runs the server over TLS, advertising the protocols it serves with the ALPN.
+    ///
+    /// The `h2` is offered with the `http2` feature and the `http/1.1` with the `http1`
+    /// feature unless the [`Builder::http2_only`] is set. Connections which fail
+    /// the handshake or don't finish it within the [`Builder::tls_handshake_timeout`]
+    /// are dropped without stopping the server.
+    #[cfg(feature = "rustls")]
+    pub async fn run_tls(
+        self,
+        addr: SocketAddr,
+        config: tokio_rustls::rustls::ServerConfig,
+    ) -> Result<(), RunError> {
+        let incoming = self.listen(addr)?;
+        self.serve_tls(incoming, config).await
     }
-}
 
-// Two lifetimes to workaround clippy bug: https://github.com/rust-lang/rust-clippy/issues/5787
-async fn parse_request<'a, 'b>(
-    parts: &'a request::Parts,
-    body: Body,
-    conf: Arc<Config>,
-    buf: &'b mut Bytes,
-) -> Result<&'b str, Box<BaseError>> {
-    let method: SupportedMethod =
-        parts
-            .method
-            .clone()
-            .try_into()
-            .map_err(|_| BaseError::MethodNotAllowed {
-                allowed: SupportedMethod::iter().collect(),
-            })?;
+    #[cfg(feature = "rustls")]
+    async fn serve_tls(
+        self,
+        incoming: Incoming,
+        mut config: tokio_rustls::rustls::ServerConfig,
+    ) -> Result<(), RunError> {
+        config.alpn_protocols = self.config.alpn_protocols();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let handshake_timeout = self
+            .config
+            .tls_handshake_timeout
+            .unwrap_or(DEFAULT_TLS_HANDSHAKE_TIMEOUT);
+        let incoming = tls::TlsIncoming::new(incoming, acceptor, handshake_timeout);
 
-    if !method.request_has_body() {
-        return Ok("");
+        self.server(incoming)
+            .serve(self)
+            .await
+            .map_err(RunError::Serve)
     }
 
-    // variable to satisfy clippy
-    let content_length_header = header::CONTENT_LENGTH;
-    let content_length: usize = parts
-        .headers
-        .get(&content_length_header)
-        .ok_or(BaseError::LengthRequired)?
-        .to_str()
-        .map_err(|_| BaseError::LengthRequired)?
-        .parse()
-        .map_err(|_| BaseError::LengthRequired)?;
+    #[cfg(feature = "tokio-runtime")]
+    fn bind(
+        &self,
+        addr: SocketAddr,
+    ) -> Result<(hyper::server::Builder<Incoming>, SocketAddr), RunError> {
+        let incoming = self.listen(addr)?;
+        let local_addr = incoming.local_addr();
 
-    if let Some(max_length) = conf.max_request_length {
-        if content_length > max_length {
-            return Err(BaseError::PayloadTooLarge.into());
-        }
+        Ok((self.server(incoming), local_addr))
     }
 
     #[cfg(feature = "tokio-runtime")]
-    let buffer = if let Some(timeout) = conf.request_read_timeout {
-        tokio::time::timeout(timeout, hyper::body::to_bytes(body))
-            .await
-            .map_err(|_| BaseError::RequestTimeout)?
-    } else {
-        hyper::body::to_bytes(body).await
-    };
+    fn listen(&self, addr: SocketAddr) -> Result<Incoming, RunError> {
+        let incoming =
+            AddrIncoming::bind(&addr).map_err(|source| RunError::Bind { addr, source })?;
 
-    #[cfg(not(feature = "tokio-runtime"))]
-    let buffer = hyper::body::to_bytes(body).await;
+        Ok(Incoming::new(incoming, &self.config))
+    }
 
-    *buf = buffer.map_err(|err| {
-        BaseError::Other(DynError {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            error: Some(err.into()),
-        })
-    })?;
+    /// Server over the `incoming` with the protocol options of the config.
+    #[cfg(feature = "tokio-runtime")]
+    fn server<I>(&self, incoming: I) -> hyper::server::Builder<I> {
+        #[allow(unused_mut)]
+        let mut server = Server::builder(incoming);
 
-    let body = std::str::from_utf8(&**buf).map_err(|_| BaseError::BodyNotUtf8)?;
+        #[cfg(feature = "http2")]
+        {
+            server = server.http2_only(self.config.http2_only);
+        }
 
-    Ok(body)
+        #[cfg(feature = "http1")]
+        {
+            if let Some(timeout) = self.config.header_read_timeout {
+                server = server.http1_header_read_timeout(timeout);
+            }
+        }
+
+        server
+    }
 }
 
-impl<T, H> Clone for Service<T, H>
-where
-    T: Send + Sync + 'static + ?Sized,
-    H: for<'a> Fn(
-            Arc<T>,
-            Request<Result<&'a str, Box<BaseError>>>,
-        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
-        + Clone
-        + Send
-        + Sync
-        + 'static,
-{
-    fn clone(&self) -> Self {
-        Service {
-            router: self.router.clone(),
-            config: Arc::clone(&self.config),
+#[cfg(feature = "tokio-runtime")]
+impl Limiter {
+    fn poll_ready(&mut self, config: &Config, cx: &mut Context<'_>) -> Poll<()> {
+        let semaphore = match &config.concurrency_limit {
+            Some(semaphore) => semaphore,
+            None => return Poll::Ready(()),
+        };
+        if semaphore.available_permits() > 0 {
+            self.acquiring = None;
+            return Poll::Ready(());
+        }
+
+        // Acquiring registers the waker, but the permit is given back right away.
+        let acquiring = self.acquiring.get_or_insert_with(|| {
+            let semaphore = Arc::clone(semaphore);
+            Box::pin(async move {
+                // The semaphore is never closed.
+                semaphore.acquire_owned().await.unwrap()
+            })
+        });
+
+        match acquiring.as_mut().poll(cx) {
+            Poll::Ready(_permit) => {
+                self.acquiring = None;
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
         }
     }
 }
 
-impl OutBuffer {
-    pub fn empty() -> Self {
-        String::new().into()
+#[cfg(feature = "tokio-runtime")]
+impl fmt::Debug for Limiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Limiter")
+            .field("acquiring", &self.acquiring.is_some())
+            .finish()
     }
 }
 
-impl From<String> for OutBuffer {
-    fn from(s: String) -> Self {
-        Self { inner: Some(s) }
-    }
+/// Spawns the connections so they can be aborted at once.
+#[cfg(feature = "tokio-runtime")]
+#[derive(Debug, Clone, Default)]
+struct ConnExecutor {
+    tasks: Arc<Mutex<tokio::task::JoinSet<()>>>,
 }
 
-impl hyper::body::HttpBody for OutBuffer {
-    type Data = Cursor<Vec<u8>>;
-    type Error = Infallible;
+#[cfg(feature = "tokio-runtime")]
+impl ConnExecutor {
+    fn abort_all(&self) {
+        self.tasks.lock().unwrap().abort_all();
+    }
+}
 
-    fn poll_data(
-        mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
-    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
-        Poll::Ready(self.inner.take().map(|v| Ok(Cursor::new(v.into_bytes()))))
+#[cfg(feature = "tokio-runtime")]
+impl<F> hyper::rt::Executor<F> for ConnExecutor
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        let mut tasks = self.tasks.lock().unwrap();
+        // Reap the finished connections.
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(fut);
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn max_reqeust_length(mut self, length: usize) -> Self {
+        self.config.max_request_length = Some(length);
+        self
+    }
+
+    /// Rejects requests whose target is longer than the `length` with `414 URI Too Long`.
+    pub fn max_uri_length(mut self, length: usize) -> Self {
+        self.config.max_uri_length = Some(length);
+        self
+    }
+
+    /// Requests with the `Content-Length` larger than the `threshold` are not buffered,
+    /// but handed to the handler as the [`StreamingBody`].
+    pub fn request_body_streaming_threshold(mut self, threshold: usize) -> Self {
+        self.config.streaming_threshold = Some(threshold);
+        self
+    }
+
+    /// Reads the request bodies up to `capacity` bytes into the buffers recycled
+    /// across the requests, keeping up to `max_buffers` of them when idle.
+    ///
+    /// It saves the allocations of the servers handling many small requests.
+    /// Larger bodies are read into the fresh buffers as usual.
+    pub fn request_body_buffer_reuse(mut self, capacity: usize, max_buffers: usize) -> Self {
+        self.config.body_buffer_pool = Some(Arc::new(BufferPool {
+            capacity,
+            max_buffers,
+            buffers: Mutex::default(),
+        }));
+        self
+    }
+
+    #[cfg(feature = "tokio-runtime")]
+    pub fn request_read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Fails the request with the `408 Request Timeout` if the client sends the body
+    /// slower than `bytes_per_sec`, measured over every second.
+    ///
+    /// Unlike the `request_read_timeout`, large bodies sent at a steady pace
+    /// are not cut off, while slow-trickle uploads are dropped early.
+    #[cfg(feature = "tokio-runtime")]
+    pub fn min_body_rate(mut self, bytes_per_sec: u64) -> Self {
+        self.config.min_body_rate = Some(bytes_per_sec);
+        self
+    }
+
+    /// Closes the connection if the client doesn't send the whole request header
+    /// within the `timeout`, unlike the `request_read_timeout` which only bounds the body.
+    #[cfg(all(feature = "http1", feature = "tokio-runtime"))]
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Closes the connection if writing the response stalls for the `timeout`,
+    /// like when the client stops reading a large body.
+    ///
+    /// It bounds the time the socket isn't accepting the response,
+    /// while the `request_read_timeout` bounds the time to receive the request.
+    #[cfg(feature = "tokio-runtime")]
+    pub fn response_write_timeout(mut self, timeout: Duration) -> Self {
+        self.config.response_write_timeout = Some(timeout);
+        self
+    }
+
+    /// Drops the TLS connection if the client doesn't finish the handshake within the `timeout`,
+    /// which is the [`DEFAULT_TLS_HANDSHAKE_TIMEOUT`] by default.
+    #[cfg(feature = "rustls")]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.config.tls_handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Replaces the headers which are redacted as `***` when the FTL logs the requests,
+    /// which are the [`DEFAULT_SENSITIVE_HEADERS`] by default.
+    ///
+    /// Their values are also marked as sensitive, so the `Debug` output
+    /// of the request within the handler doesn't show them either.
+    pub fn with_sensitive_headers<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = header::HeaderName>,
+    {
+        self.config.sensitive_headers = Some(names.into_iter().collect());
+        self
+    }
+
+    /// Resolves the [`ClientIp`] from the `X-Forwarded-For` header,
+    /// assuming `depth` trusted proxies are in front of this server.
+    ///
+    /// The `depth`-th address from the last one is taken. Walking from the right,
+    /// the last valid hop before a malformed entry is taken instead, or the peer address.
+    /// If unset, the header is ignored entirely to avoid spoofing.
+    pub fn trust_proxy(mut self, depth: usize) -> Self {
+        self.config.trust_proxy = Some(depth);
+        self
+    }
+
+    /// Redirects the plain HTTP requests to the `https://` URL with `308 Permanent Redirect`,
+    /// for the servers behind the proxy terminating the TLS.
+    ///
+    /// The scheme is taken from the `X-Forwarded-Proto` header like the [`trust_proxy`],
+    /// so it has no effect without it. Requests forwarded as HTTPS are never redirected,
+    /// which prevents the redirect loops.
+    ///
+    /// [`trust_proxy`]: Builder::trust_proxy
+    pub fn enforce_https_redirect(mut self) -> Self {
+        self.config.https_redirect = true;
+        self
+    }
+
+    /// Handles the `Expect` header of the requests, as the RFC 9110 requires.
+    ///
+    /// The `100 Continue` is sent by the hyper once the body is read, so the requests
+    /// rejected before it, like the ones whose `Content-Length` exceeds
+    /// the [`max_reqeust_length`], get the final response without the upload.
+    /// Such responses close the connection, since the client may send the body anyway.
+    /// Other expectations than the `100-continue` are rejected with `417 Expectation Failed`.
+    ///
+    /// [`max_reqeust_length`]: Builder::max_reqeust_length
+    pub fn enable_expect_continue(mut self) -> Self {
+        self.config.expect_continue = true;
+        self
+    }
+
+    /// Serves HTTP/2 only, which allows the HTTP/2 over cleartext TCP
+    /// with the prior knowledge.
+    #[cfg(feature = "http2")]
+    pub fn http2_only(mut self, enabled: bool) -> Self {
+        self.config.http2_only = enabled;
+        self
+    }
+
+    /// Compresses the response bodies with the `algorithm` at the `level`,
+    /// if the client accepts it.
+    ///
+    /// Call it for each algorithm to enable. When the client accepts several of them,
+    /// the one with the highest q-value in the `Accept-Encoding` is picked,
+    /// preferring the one enabled first on ties.
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn compression(mut self, algorithm: Algorithm, level: u32) -> Self {
+        let enabled = &mut self.config.compression;
+        match enabled
+            .iter_mut()
+            .find(|(enabled, _)| *enabled == algorithm)
+        {
+            Some(entry) => entry.1 = level,
+            None => enabled.push((algorithm, level)),
+        }
+        self
+    }
+
+    /// Limits the number of requests handled at the same time.
+    ///
+    /// The service isn't ready while the limit is reached,
+    /// so the connections stop reading new requests until a permit is released.
+    /// Idle keep-alive connections don't hold the permits.
+    #[cfg(feature = "tokio-runtime")]
+    pub fn concurrency_limit(mut self, limit: usize) -> Self {
+        self.config.concurrency_limit = Some(Arc::new(tokio::sync::Semaphore::new(limit)));
+        self
+    }
+
+    /// Limits the number of requests handled at the same time for each [`ClientIp`],
+    /// so a single client can't take the whole [`concurrency_limit`].
+    ///
+    /// Requests beyond the limit are rejected with `429 Too Many Requests`.
+    /// Set the [`trust_proxy`] to count the clients behind the proxy separately.
+    /// Requests whose IP is unknown share a single limit.
+    ///
+    /// [`concurrency_limit`]: Builder::concurrency_limit
+    /// [`trust_proxy`]: Builder::trust_proxy
+    pub fn request_concurrency_per_ip(mut self, limit: usize) -> Self {
+        self.config.per_ip_concurrency = Some(Arc::new(PerIpLimit {
+            limit,
+            in_flight: Mutex::default(),
+        }));
+        self
+    }
+
+    /// Limits the number of open connections, including the idle keep-alive ones.
+    ///
+    /// Connections accepted beyond the limit are closed right away,
+    /// until some of the live connections are closed.
+    #[cfg(feature = "tokio-runtime")]
+    pub fn max_connections(mut self, limit: usize) -> Self {
+        self.config.max_connections = Some(limit);
+        self
+    }
+
+    /// Normalizes the request path before routing. It's `Off` by default.
+    pub fn normalize_path(mut self, normalization: PathNormalization) -> Self {
+        self.config.path_normalization = normalization;
+        self
+    }
+
+    /// Aborts the connections still in flight when the `timeout` elapses
+    /// after the shutdown signal of the [`Service::run_with_shutdown`].
+    #[cfg(feature = "tokio-runtime")]
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.config.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Panics if the successful response body doesn't match the response type
+    /// of the route it's recorded for, to catch drift between the handlers and the document.
+    ///
+    /// It's only checked in debug builds.
+    pub fn validate_responses(mut self) -> Self {
+        self.config.validate_responses = true;
+        self
+    }
+
+    /// Warns via the `tracing` if the response body is longer than `limit` bytes,
+    /// to catch the unbounded result sets like the missing pagination.
+    ///
+    /// If `strict`, such responses are replaced with the `500 Internal Server Error` instead.
+    /// It's only checked in debug builds.
+    pub fn max_response_length(mut self, limit: usize, strict: bool) -> Self {
+        self.config.max_response_length = Some((limit, strict));
+        self
+    }
+
+    /// Rounds the floating point numbers within the JSON responses
+    /// to the `decimals` places, e.g. `1.2345` into `1.23` with the `2`.
+    ///
+    /// Rounding is half away from zero on the decimal form of the number,
+    /// so the `1.005` becomes the `1.01` despite its binary value being slightly less.
+    ///
+    /// Integers are kept as is. The body is parsed and serialized again,
+    /// so without the `ordered-json` feature the keys may be reordered.
+    pub fn json_number_precision(mut self, decimals: u32) -> Self {
+        self.config.json_number_precision = Some(decimals);
+        self
+    }
+
+    /// Translates the messages of the error responses into the languages
+    /// of the `Accept-Language` header, tried in the order of preference.
+    ///
+    /// Only the message is replaced, which is the `error` of the [`DynError`],
+    /// so the structure of the body stays the same. If the `localizer` returns `None`
+    /// for every language, the message is kept as is. Translated responses
+    /// have the `Content-Language` header.
+    pub fn error_localizer<F>(mut self, localizer: F) -> Self
+    where
+        F: Fn(&BaseError, &LanguageTag) -> Option<String> + Send + Sync + 'static,
+    {
+        self.config.error_localizer = Some(ErrorLocalizer(Box::new(localizer)));
+        self
+    }
+
+    /// Adds the `headers` to the responses of every route, unless the handler
+    /// or the [`Router::with_default_response_headers`] has set them already.
+    pub fn default_response_headers(mut self, headers: HeaderMap) -> Self {
+        self.config.default_response_headers = headers;
+        self
+    }
+
+    /// Sets the `Cache-Control` of the responses without one, to the `directive`
+    /// for the 2xx responses and to the `no-store` for the 4xx and 5xx ones
+    /// so the errors aren't cached by accident. Other statuses are left as is.
+    pub fn default_cache_control(mut self, directive: HeaderValue) -> Self {
+        self.config.default_cache_control = Some(directive);
+        self
+    }
+
+    /// Sets how strictly the JSON request bodies are accepted by the routes,
+    /// unless overridden by the [`Router::expect_json`].
+    pub fn expect_json(mut self, expect: ExpectJson) -> Self {
+        self.config.expect_json = Some(expect);
+        self
+    }
+
+    /// Calls the `observer` for each request once it's received, before the routing.
+    ///
+    /// It only observes the request, for the metrics and the audit logs.
+    /// Use the middlewares to alter the flow.
+    pub fn on_request<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&request::Parts) + Send + Sync + 'static,
+    {
+        self.config.observers.on_request = Some(Box::new(observer));
+        self
+    }
+
+    /// Calls the `observer` for each response with its status and the time taken
+    /// since the request is received.
+    ///
+    /// The parts of the request are the ones received, without the extensions.
+    /// It isn't called if the handler fails, which closes the connection instead.
+    pub fn on_response<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&request::Parts, StatusCode, Duration) + Send + Sync + 'static,
+    {
+        self.config.observers.on_response = Some(Box::new(observer));
+        self
+    }
+
+    pub fn build<T, H>(self, router: Router<T, H>) -> Service<T, H>
+    where
+        T: Send + Sync + 'static + ?Sized,
+        H: for<'a> Fn(
+                Arc<T>,
+                Request<Result<&'a str, Box<BaseError>>>,
+            ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        Service {
+            router,
+            config: Arc::new(self.config),
+            remote_addr: None,
+            #[cfg(feature = "tokio-runtime")]
+            limiter: Limiter::default(),
+        }
+    }
+}
+
+impl<'c, C, T, H> HyperService<&'c C> for Service<T, H>
+where
+    C: RemoteAddr,
+    T: Send + Sync + 'static + ?Sized,
+    H: for<'a> Fn(
+            Arc<T>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    type Response = Self;
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, conn: &'c C) -> Self::Future {
+        let mut service = self.clone();
+        service.remote_addr = conn.remote_addr();
+        ready(Ok(service))
+    }
+}
+
+impl<T, H> HyperService<Request<Body>> for Service<T, H>
+where
+    T: Send + Sync + 'static + ?Sized,
+    H: for<'a> Fn(
+            Arc<T>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    type Response = Response<OutBuffer>;
+    type Error = BoxError;
+    // TODO: apply existential type when available
+    type Future = BoxFuture<'static, Result<Response<OutBuffer>, BoxError>>;
+
+    #[cfg(feature = "tokio-runtime")]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.limiter.poll_ready(&self.config, cx).map(Ok)
+    }
+
+    #[cfg(not(feature = "tokio-runtime"))]
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let router = self.router.clone();
+        let config = Arc::clone(&self.config);
+        let remote_addr = self.remote_addr;
+
+        let (mut parts, body) = req.into_parts();
+        let received = config
+            .observers
+            .on_response
+            .as_ref()
+            .map(|_| (copy_parts(&parts), Instant::now()));
+
+        let resp = Box::pin(async move {
+            // Held until the response is produced.
+            #[cfg(feature = "tokio-runtime")]
+            let _permit = match &config.concurrency_limit {
+                // The semaphore is never closed.
+                Some(semaphore) => Some(Arc::clone(semaphore).acquire_owned().await.unwrap()),
+                None => None,
+            };
+
+            let sensitive = config.sensitive_headers();
+            for (name, value) in parts.headers.iter_mut() {
+                if sensitive.contains(name) {
+                    value.set_sensitive(true);
+                }
+            }
+            tracing::trace!(
+                method = %parts.method,
+                path = parts.uri.path(),
+                headers = ?RedactedHeaders {
+                    headers: &parts.headers,
+                    sensitive,
+                },
+                "request received",
+            );
+            if let Some(on_request) = &config.observers.on_request {
+                on_request(&parts);
+            }
+
+            if let Some(location) = https_redirect(&parts, &config) {
+                let mut resp = Response::new(OutBuffer::empty());
+                *resp.status_mut() = StatusCode::PERMANENT_REDIRECT;
+                resp.headers_mut()
+                    .insert(header::LOCATION, location.try_into()?);
+                return Ok(resp);
+            }
+
+            if let Some(path) = config.path_normalization.normalize(parts.uri.path()) {
+                let target = match parts.uri.query() {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path,
+                };
+
+                if config.path_normalization == PathNormalization::Redirect {
+                    let mut resp = Response::new(OutBuffer::empty());
+                    *resp.status_mut() = StatusCode::PERMANENT_REDIRECT;
+                    resp.headers_mut()
+                        .insert(header::LOCATION, target.try_into()?);
+                    return Ok(resp);
+                }
+
+                let mut uri = http::uri::Parts::from(parts.uri);
+                uri.path_and_query = Some(target.try_into()?);
+                parts.uri = http::Uri::from_parts(uri)?;
+            }
+
+            let ip = client_ip(&parts, &config, remote_addr);
+            if let Some(ip) = ip {
+                parts.extensions.insert(ClientIp(ip));
+            }
+
+            if let Some(expect) = config.expect_json {
+                parts.extensions.insert(expect);
+            }
+
+            let _ip_permit = match &config.per_ip_concurrency {
+                Some(limit) => match limit.try_acquire(ip) {
+                    Some(permit) => Some(permit),
+                    None => {
+                        tracing::debug!(?ip, "too many requests in flight from the client");
+                        let err = DynError::new(
+                            StatusCode::TOO_MANY_REQUESTS,
+                            "too many requests in flight from the client",
+                        );
+                        let resp = crate::error::error_response(&BaseError::Other(err));
+                        return Ok(resp.map(From::from));
+                    }
+                },
+                None => None,
+            };
+
+            let expects_continue = match parts.headers.get(header::EXPECT) {
+                Some(value) if config.expect_continue => {
+                    if !value.as_bytes().eq_ignore_ascii_case(b"100-continue") {
+                        let err = DynError::new(
+                            StatusCode::EXPECTATION_FAILED,
+                            "only the 100-continue is supported",
+                        );
+                        let mut resp = crate::error::error_response(&BaseError::Other(err));
+                        resp.headers_mut()
+                            .insert(header::CONNECTION, HeaderValue::from_static("close"));
+                        return Ok(resp.map(From::from));
+                    }
+                    true
+                }
+                _ => false,
+            };
+
+            #[cfg(debug_assertions)]
+            let route = (parts.method.clone(), parts.uri.path().to_owned());
+            #[cfg(any(feature = "gzip", feature = "brotli"))]
+            let req_headers = parts.headers.clone();
+            let languages = match &config.error_localizer {
+                Some(_) => parts
+                    .headers
+                    .get(header::ACCEPT_LANGUAGE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(LanguageTag::parse_accept_language)
+                    .unwrap_or_default(),
+                None => vec![],
+            };
+
+            let mut buf = Bytes::new();
+            let body = parse_request(&mut parts, body, Arc::clone(&config), &mut buf).await;
+            // The body isn't read if the request is rejected, so the 100 Continue isn't sent.
+            let rejected_early = expects_continue && body.is_err();
+            let mut resp = (router.handler)(router.app, Request::from_parts(parts, body)).await?;
+            if let Some(pool) = &config.body_buffer_pool {
+                pool.put(buf);
+            }
+            // The errors get the `no-store` before the defaults meant for the contents,
+            // while the directive of the 2xx responses is set after them below.
+            let status = resp.status();
+            if !status.is_success() {
+                if let Some(directive) = &config.default_cache_control {
+                    insert_cache_control(resp.headers_mut(), status, directive.clone());
+                }
+            }
+            insert_router_headers(&mut resp);
+            insert_default_headers(resp.headers_mut(), &config.default_response_headers);
+            if rejected_early {
+                resp.headers_mut()
+                    .insert(header::CONNECTION, HeaderValue::from_static("close"));
+            }
+
+            #[cfg(debug_assertions)]
+            let resp = match config.max_response_length {
+                Some((limit, strict)) if resp.body().len() > limit => {
+                    let (method, path) = &route;
+                    let length = resp.body().len();
+
+                    if strict {
+                        let reason = format!(
+                            "response of {} {} is {} bytes, longer than the limit {}",
+                            method, path, length, limit
+                        );
+                        let err = DynError::new(StatusCode::INTERNAL_SERVER_ERROR, reason);
+                        crate::error::error_response(&BaseError::Other(err))
+                    } else {
+                        tracing::warn!(%method, path, length, limit, "response too long");
+                        resp
+                    }
+                }
+                _ => resp,
+            };
+
+            #[cfg(debug_assertions)]
+            {
+                if config.validate_responses && resp.status().is_success() {
+                    let (method, path) = route;
+                    let recorded = router.routes.iter().find(|route| {
+                        SupportedMethod::new(method.clone()).ok() == Some(route.method)
+                            && route.path == path
+                    });
+
+                    if let Some(route) = recorded {
+                        if let Err(err) = (route.validate_response)(resp.body()) {
+                            panic!(
+                                "response of {} {} doesn't match its schema: {}",
+                                method, path, err
+                            );
+                        }
+                    }
+                }
+            }
+
+            let resp = match &config.error_localizer {
+                Some(localizer) if !languages.is_empty() => {
+                    localize_error(resp, localizer, &languages)
+                }
+                _ => resp,
+            };
+
+            let resp = match config.json_number_precision {
+                Some(decimals) => round_json_floats(resp, decimals),
+                None => resp,
+            };
+
+            #[cfg(any(feature = "gzip", feature = "brotli"))]
+            let resp = compression::compress(&config.compression, &req_headers, resp)?;
+
+            let (mut parts, body) = resp.into_parts();
+            let mut body = OutBuffer::from(body);
+            if let Some(events) = parts.extensions.remove::<EventStream>() {
+                body = body.with_chunks(events.into_chunks());
+            }
+            if let Some(Trailers(trailers)) = parts.extensions.remove() {
+                body = body.with_trailers(trailers);
+            }
+
+            Ok(Response::from_parts(parts, body))
+        });
+
+        let resp: Self::Future = match self.config.default_cache_control.clone() {
+            Some(directive) => Box::pin(async move {
+                let mut resp = resp.await?;
+                let status = resp.status();
+                insert_cache_control(resp.headers_mut(), status, directive);
+                Ok(resp)
+            }),
+            None => resp,
+        };
+
+        let (parts, started) = match received {
+            Some(received) => received,
+            None => return resp,
+        };
+        let config = Arc::clone(&self.config);
+        Box::pin(async move {
+            let resp = resp.await?;
+            if let Some(on_response) = &config.observers.on_response {
+                on_response(&parts, resp.status(), started.elapsed());
+            }
+            Ok(resp)
+        })
+    }
+}
+
+/// Sets the `Cache-Control` by the status, unless it's already set.
+fn insert_cache_control(headers: &mut HeaderMap, status: StatusCode, directive: HeaderValue) {
+    if headers.contains_key(header::CACHE_CONTROL) {
+        return;
+    }
+    if status.is_success() {
+        headers.insert(header::CACHE_CONTROL, directive);
+    } else if status.is_client_error() || status.is_server_error() {
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+}
+
+/// Copies the parts of the request, except the extensions which can't be cloned.
+fn copy_parts(parts: &request::Parts) -> request::Parts {
+    let mut req = Request::new(());
+    *req.method_mut() = parts.method.clone();
+    *req.uri_mut() = parts.uri.clone();
+    *req.version_mut() = parts.version;
+    *req.headers_mut() = parts.headers.clone();
+    req.into_parts().0
+}
+
+/// Replaces the message of the `BaseError` or the `DynError` body
+/// with the first translation the `localizer` provides.
+fn localize_error(
+    mut resp: Response<String>,
+    localizer: &ErrorLocalizer,
+    languages: &[LanguageTag],
+) -> Response<String> {
+    if !resp.status().is_client_error() && !resp.status().is_server_error() {
+        return resp;
+    }
+
+    let (error, wrapped) = match serde_json::from_str::<BaseError>(resp.body()) {
+        Ok(error) => (error, true),
+        Err(_) => match serde_json::from_str::<DynError>(resp.body()) {
+            Ok(error) => (BaseError::Other(error), false),
+            Err(_) => return resp,
+        },
+    };
+    let status = match &error {
+        BaseError::Other(DynError { status, .. }) => *status,
+        _ => return resp,
+    };
+
+    let translated = languages
+        .iter()
+        .find_map(|lang| Some((lang, (localizer.0)(&error, lang)?)));
+    let (lang, message) = match translated {
+        Some(translated) => translated,
+        None => return resp,
+    };
+
+    let error = DynError::new(status, message);
+    let body = if wrapped {
+        serde_json::to_string(&BaseError::Other(error))
+    } else {
+        serde_json::to_string(&error)
+    };
+    if let (Ok(body), Ok(lang)) = (body, lang.as_str().try_into()) {
+        *resp.body_mut() = body;
+        resp.headers_mut().insert(header::CONTENT_LANGUAGE, lang);
+    }
+
+    resp
+}
+
+/// Rounds half away from zero on the shortest decimal form of the `float`
+/// rather than its binary value, so the `1.005` becomes the `1.01`.
+fn round_decimal(float: f64, decimals: usize) -> f64 {
+    // The `Display` of the `f64` never uses the exponent.
+    let text = float.abs().to_string();
+    let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+    if frac.len() <= decimals {
+        return float;
+    }
+
+    let mut digits: Vec<u8> = int.bytes().chain(frac.bytes().take(decimals)).collect();
+    if frac.as_bytes()[decimals] >= b'5' {
+        let mut carry = true;
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                carry = false;
+                break;
+            }
+        }
+        if carry {
+            digits.insert(0, b'1');
+        }
+    }
+
+    let split = digits.len() - decimals;
+    let rounded = format!(
+        "{}.{}0",
+        std::str::from_utf8(&digits[..split]).unwrap(),
+        std::str::from_utf8(&digits[split..]).unwrap()
+    );
+    rounded
+        .parse::<f64>()
+        .map_or(float, |rounded| rounded.copysign(float))
+}
+
+fn round_json_floats(resp: Response<String>, decimals: u32) -> Response<String> {
+    fn round(value: &mut serde_json::Value, decimals: usize) {
+        match value {
+            serde_json::Value::Number(number) if number.is_f64() => {
+                let rounded = number.as_f64().map(|float| round_decimal(float, decimals));
+                if let Some(rounded) = rounded.and_then(serde_json::Number::from_f64) {
+                    *number = rounded;
+                }
+            }
+            serde_json::Value::Array(array) => {
+                array.iter_mut().for_each(|elem| round(elem, decimals))
+            }
+            serde_json::Value::Object(object) => {
+                object.values_mut().for_each(|elem| round(elem, decimals))
+            }
+            _ => {}
+        }
+    }
+
+    let is_json = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return resp;
+    }
+
+    let (parts, body) = resp.into_parts();
+    let mut value: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(value) => value,
+        Err(_) => return Response::from_parts(parts, body),
+    };
+    round(&mut value, decimals as usize);
+
+    let body = serde_json::to_string(&value).unwrap_or(body);
+    Response::from_parts(parts, body)
+}
+
+/// Returns the `https://` URL to redirect if the request is forwarded as the plain HTTP.
+fn https_redirect(parts: &request::Parts, conf: &Config) -> Option<String> {
+    let depth = match conf.trust_proxy {
+        Some(depth) if conf.https_redirect && depth > 0 => depth,
+        _ => return None,
+    };
+
+    let protos: Vec<&str> = parts
+        .headers
+        .get_all("x-forwarded-proto")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    let proto = match protos.len().checked_sub(depth) {
+        Some(idx) => protos[idx],
+        None => protos.first()?,
+    };
+    if !proto.eq_ignore_ascii_case("http") {
+        return None;
+    }
+
+    let host = parts.headers.get(header::HOST)?.to_str().ok()?;
+    let target = parts
+        .uri
+        .path_and_query()
+        .map_or("/", |target| target.as_str());
+
+    Some(format!("https://{}{}", host, target))
+}
+
+fn client_ip(
+    parts: &request::Parts,
+    conf: &Config,
+    remote_addr: Option<SocketAddr>,
+) -> Option<IpAddr> {
+    let peer = remote_addr.map(|addr| addr.ip());
+    let depth = match conf.trust_proxy {
+        Some(depth) if depth > 0 => depth,
+        _ => return peer,
+    };
+
+    let chain: Vec<&str> = parts
+        .headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    // Walks the trusted hops from the right, stopping at the first malformed one
+    // so the client can't hide behind a garbage entry.
+    let mut ip = peer;
+    for hop in chain.iter().rev().take(depth) {
+        match hop.trim().parse() {
+            Ok(addr) => ip = Some(addr),
+            Err(_) => break,
+        }
+    }
+    ip
+}
+
+async fn parse_request<'b>(
+    parts: &mut request::Parts,
+    body: Body,
+    conf: Arc<Config>,
+    buf: &'b mut Bytes,
+) -> Result<&'b str, Box<BaseError>> {
+    if let Some(max_length) = conf.max_uri_length {
+        let length = match parts.uri.path_and_query() {
+            Some(target) => target.as_str().len(),
+            None => parts.uri.to_string().len(),
+        };
+
+        if length > max_length {
+            return Err(BaseError::UriTooLong.into());
+        }
+    }
+
+    let method: SupportedMethod =
+        parts
+            .method
+            .clone()
+            .try_into()
+            .map_err(|_| BaseError::MethodNotAllowed {
+                allowed: SupportedMethod::iter().collect(),
+            })?;
+
+    if !method.request_has_body() {
+        return Ok("");
+    }
+
+    // variable to satisfy clippy
+    let content_length_header = header::CONTENT_LENGTH;
+    let content_length: usize = parts
+        .headers
+        .get(&content_length_header)
+        .ok_or(BaseError::LengthRequired)?
+        .to_str()
+        .map_err(|_| BaseError::LengthRequired)?
+        .parse()
+        .map_err(|_| BaseError::LengthRequired)?;
+
+    if let Some(max_length) = conf.max_request_length {
+        if content_length > max_length {
+            return Err(BaseError::PayloadTooLarge.into());
+        }
+    }
+
+    if let Some(threshold) = conf.streaming_threshold {
+        if content_length > threshold {
+            parts.extensions.insert(StreamingBody(body));
+            return Ok("");
+        }
+    }
+
+    let pooled = match &conf.body_buffer_pool {
+        Some(pool) => pool.take(content_length),
+        None => None,
+    };
+
+    #[cfg(feature = "tokio-runtime")]
+    let buffer = {
+        let read = match (conf.min_body_rate, pooled) {
+            (Some(rate), pooled) => {
+                let buf = pooled.unwrap_or_else(|| BytesMut::with_capacity(content_length));
+                read_body_with_min_rate(body, buf, rate).boxed()
+            }
+            (None, Some(buf)) => read_body_into(body, buf).map(|res| res.map(Some)).boxed(),
+            (None, None) => hyper::body::to_bytes(body).map(|res| res.map(Some)).boxed(),
+        };
+
+        let buffer = if let Some(timeout) = conf.request_read_timeout {
+            tokio::time::timeout(timeout, read)
+                .await
+                .map_err(|_| BaseError::RequestTimeout)?
+        } else {
+            read.await
+        };
+
+        buffer.transpose().ok_or(BaseError::RequestTimeout)?
+    };
+
+    #[cfg(not(feature = "tokio-runtime"))]
+    let buffer = match pooled {
+        Some(buf) => read_body_into(body, buf).await,
+        None => hyper::body::to_bytes(body).await,
+    };
+
+    *buf = buffer.map_err(|err| {
+        BaseError::Other(DynError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: Some(err.into()),
+        })
+    })?;
+
+    let body = std::str::from_utf8(buf).map_err(|_| BaseError::BodyNotUtf8)?;
+
+    Ok(body)
+}
+
+/// Reads the body into the `buf`, which may be the pooled one.
+async fn read_body_into(mut body: Body, mut buf: BytesMut) -> Result<Bytes, hyper::Error> {
+    use hyper::body::HttpBody;
+
+    while let Some(chunk) = body.data().await {
+        buf.extend_from_slice(&chunk?);
+    }
+
+    Ok(buf.freeze())
+}
+
+/// Window over which the [`Builder::min_body_rate`] is measured.
+#[cfg(feature = "tokio-runtime")]
+const MIN_BODY_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Reads the body chunk by chunk, or returns `None` if fewer than
+/// `rate` bytes per second arrived within any window.
+#[cfg(feature = "tokio-runtime")]
+async fn read_body_with_min_rate(
+    mut body: Body,
+    mut buf: BytesMut,
+    rate: u64,
+) -> Result<Option<Bytes>, hyper::Error> {
+    use hyper::body::HttpBody;
+
+    let min_bytes = (rate as f64 * MIN_BODY_RATE_WINDOW.as_secs_f64()) as u64;
+    let mut window_end = tokio::time::Instant::now() + MIN_BODY_RATE_WINDOW;
+    let mut window_bytes = 0;
+
+    loop {
+        match tokio::time::timeout_at(window_end, body.data()).await {
+            Ok(Some(chunk)) => {
+                let chunk = chunk?;
+                window_bytes += chunk.len() as u64;
+                buf.extend_from_slice(&chunk);
+            }
+            Ok(None) => return Ok(Some(buf.freeze())),
+            Err(_) => {
+                if window_bytes < min_bytes {
+                    return Ok(None);
+                }
+                window_end += MIN_BODY_RATE_WINDOW;
+                window_bytes = 0;
+            }
+        }
+    }
+}
+
+impl<T, H> Clone for Service<T, H>
+where
+    T: Send + Sync + 'static + ?Sized,
+    H: for<'a> Fn(
+            Arc<T>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    fn clone(&self) -> Self {
+        Service {
+            router: self.router.clone(),
+            config: Arc::clone(&self.config),
+            remote_addr: self.remote_addr,
+            #[cfg(feature = "tokio-runtime")]
+            limiter: Limiter::default(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
+impl RemoteAddr for AddrStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(AddrStream::remote_addr(self))
+    }
+}
+
+#[cfg(test)]
+fn echo_client_ip(
+    _app: Arc<()>,
+    req: Request<Result<&str, Box<BaseError>>>,
+) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+    let ip = req
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ip| ip.0.to_string());
+    Box::pin(async move { Ok(Response::new(ip.unwrap_or_default())) })
+}
+
+#[cfg(test)]
+async fn call_service<H>(service: &mut Service<(), H>, req: Request<Body>) -> Response<String>
+where
+    H: for<'a> Fn(
+            Arc<()>,
+            Request<Result<&'a str, Box<BaseError>>>,
+        ) -> BoxFuture<'a, Result<Response<String>, BoxError>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let resp = HyperService::call(service, req).await.unwrap();
+    let (parts, body) = resp.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap();
+    Response::from_parts(parts, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn sensitive_headers_are_redacted() {
+    use crate::test_util::Captured;
+
+    fn debug_headers(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let headers = format!("{:?}", req.headers());
+        Box::pin(async move { Ok(Response::new(headers)) })
+    }
+
+    let captured = Captured::default();
+    let _guard = tracing::subscriber::set_default(captured.subscriber(tracing::Level::TRACE));
+
+    let router = Router::from_handler(Arc::new(()), debug_headers);
+    let mut service = Builder::new().build(router);
+    let req = Request::get("/")
+        .header(header::AUTHORIZATION, "Bearer hunter2")
+        .header("x-api-key", "abc123")
+        .body(Body::empty())
+        .unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert!(!resp.body().contains("hunter2"), "{}", resp.body());
+    assert!(resp.body().contains("abc123"), "{}", resp.body());
+
+    let router = Router::from_handler(Arc::new(()), debug_headers);
+    let mut service = Builder::new()
+        .with_sensitive_headers(vec![header::HeaderName::from_static("x-api-key")])
+        .build(router);
+    let req = Request::get("/")
+        .header("x-api-key", "abc123")
+        .body(Body::empty())
+        .unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert!(!resp.body().contains("abc123"), "{}", resp.body());
+
+    let log = captured.log();
+    assert!(log.contains(r#""authorization": "***""#), "{}", log);
+    assert!(log.contains(r#""x-api-key": "***""#), "{}", log);
+    assert!(!log.contains("hunter2"), "{}", log);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn trust_proxy_resolves_forwarded_ip() {
+    let router = Router::from_handler(Arc::new(()), echo_client_ip);
+    let mut service = Builder::new().trust_proxy(2).build(router);
+    service.remote_addr = Some(([10, 0, 0, 2], 4000).into());
+
+    let req = Request::get("/")
+        .header("x-forwarded-for", "6.6.6.6, 1.2.3.4")
+        .header("x-forwarded-for", "10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "1.2.3.4");
+
+    let req = Request::get("/")
+        .header("x-forwarded-for", "1.2.3.4, garbage")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "10.0.0.2");
+
+    let req = Request::get("/")
+        .header("x-forwarded-for", "garbage, 10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "10.0.0.1");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn untrusted_proxy_ignores_forwarded_ip() {
+    let router = Router::from_handler(Arc::new(()), echo_client_ip);
+    let mut service = Builder::new().build(router);
+    service.remote_addr = Some(([10, 0, 0, 2], 4000).into());
+
+    let req = Request::get("/")
+        .header("x-forwarded-for", "1.2.3.4")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "10.0.0.2");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn streaming_threshold_splits_bodies() {
+    fn echo_body(
+        _app: Arc<()>,
+        mut req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let stream = req.extensions_mut().remove::<StreamingBody>();
+        let body = req.into_body();
+
+        Box::pin(async move {
+            Ok(Response::new(match stream {
+                Some(StreamingBody(stream)) => {
+                    let bytes = hyper::body::to_bytes(stream).await?;
+                    format!("stream:{}", String::from_utf8(bytes.to_vec())?)
+                }
+                None => format!("buffered:{}", body.unwrap()),
+            }))
+        })
+    }
+
+    let router = Router::from_handler(Arc::new(()), echo_body);
+    let mut service = Builder::new()
+        .request_body_streaming_threshold(4)
+        .build(router);
+
+    let req = Request::post("/")
+        .header(header::CONTENT_LENGTH, 4)
+        .body(Body::from("tiny"))
+        .unwrap();
+    assert_eq!(
+        call_service(&mut service, req).await.body(),
+        "buffered:tiny"
+    );
+
+    let req = Request::post("/")
+        .header(header::CONTENT_LENGTH, 5)
+        .body(Body::from("large"))
+        .unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "stream:large");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn request_body_buffer_reuse_matches_fresh_buffers() {
+    fn echo_body(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let body = req.into_body().map(str::to_owned);
+        Box::pin(async move {
+            Ok(match body {
+                Ok(body) => Response::new(body),
+                Err(err) => crate::error::error_response(&*err),
+            })
+        })
+    }
+
+    let mut fresh = Builder::new().build(Router::from_handler(Arc::new(()), echo_body));
+    let mut pooled = Builder::new()
+        .request_body_buffer_reuse(64, 4)
+        .build(Router::from_handler(Arc::new(()), echo_body));
+
+    let request = |body: &str| {
+        let (mut sender, stream) = Body::channel();
+        let chunks: Vec<_> = body
+            .as_bytes()
+            .chunks(7)
+            .map(Bytes::copy_from_slice)
+            .collect();
+        tokio::spawn(async move {
+            for chunk in chunks {
+                sender.send_data(chunk).await.unwrap();
+            }
+        });
+
+        Request::post("/")
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(stream)
+            .unwrap()
+    };
+
+    for round in 0..200 {
+        // Mostly the small bodies, with the larger ones and the multibyte ones
+        // split across the chunks mixed in.
+        let body = match round % 10 {
+            0 => "x".repeat(100 + round),
+            1 => "가나다".repeat(round % 5 + 1),
+            _ => format!("{{\"round\":{}}}", round).repeat(round % 4 + 1),
+        };
+
+        let expected = call_service(&mut fresh, request(&body)).await;
+        let actual = call_service(&mut pooled, request(&body)).await;
+        assert_eq!(actual.status(), expected.status());
+        assert_eq!(actual.body(), expected.body());
+        assert_eq!(actual.body(), &body);
+    }
+
+    let pool = pooled.config.body_buffer_pool.as_ref().unwrap();
+    let buffers = pool.buffers.lock().unwrap();
+    assert!(!buffers.is_empty() && buffers.len() <= 4);
+    assert!(buffers
+        .iter()
+        .all(|buf| buf.is_empty() && buf.capacity() >= 64));
+}
+
+#[cfg(all(test, feature = "http2"))]
+#[tokio::test]
+async fn http2_only_serves_prior_knowledge() {
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let (_server, addr) = Builder::new()
+        .http2_only(true)
+        .build(router)
+        .spawn(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<Body>();
+    let uri: http::Uri = format!("http://{}/h2c", addr).parse().unwrap();
+
+    let resp = client.get(uri).await.unwrap();
+    assert_eq!(resp.version(), http::Version::HTTP_2);
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "/h2c");
+}
+
+#[cfg(all(test, feature = "http1"))]
+#[tokio::test]
+async fn run_with_ready_serves_once_signaled() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let (ready, bound) = tokio::sync::oneshot::channel();
+    let server = tokio::spawn(router.run_with_ready(([127, 0, 0, 1], 0).into(), ready));
+
+    let addr = bound.await.unwrap();
+    assert_ne!(addr.port(), 0);
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /ready HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await.unwrap();
+    let resp = String::from_utf8(buf).unwrap();
+    assert!(resp.starts_with("HTTP/1.1 200"), "{}", resp);
+    assert!(resp.ends_with("/ready"), "{}", resp);
+
+    server.abort();
+}
+
+#[cfg(all(test, feature = "http1"))]
+#[tokio::test]
+async fn expect_continue_rejects_oversized_before_upload() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn echo_body(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let resp = match req.into_body() {
+            Ok(body) => Response::new(body.to_owned()),
+            Err(err) => crate::error::error_response(&*err),
+        };
+        Box::pin(async move { Ok(resp) })
+    }
+
+    let service = Builder::new()
+        .max_reqeust_length(16)
+        .enable_expect_continue()
+        .build(Router::from_handler(Arc::new(()), echo_body));
+    let (ready, bound) = tokio::sync::oneshot::channel();
+    tokio::spawn(service.run_with_ready(([127, 0, 0, 1], 0).into(), ready));
+    let addr = bound.await.unwrap();
+
+    // Only the headers are sent, the client waits for the server.
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000\r\n\
+              Expect: 100-continue\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+        .await
+        .expect("connection wasn't closed")
+        .unwrap();
+    let resp = String::from_utf8(buf).unwrap();
+    assert!(resp.starts_with("HTTP/1.1 413"), "{}", resp);
+    assert!(!resp.contains("100 Continue"), "{}", resp);
+    assert!(resp.contains("connection: close"), "{}", resp);
+
+    // Acceptable requests are approved, and then the body is read.
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\
+              Expect: 100-continue\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut interim = [0; 25];
+    stream.read_exact(&mut interim).await.unwrap();
+    assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+    stream.write_all(b"hello").await.unwrap();
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await.unwrap();
+    let resp = String::from_utf8(buf).unwrap();
+    assert!(resp.starts_with("HTTP/1.1 200"), "{}", resp);
+    assert!(resp.ends_with("hello"), "{}", resp);
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nExpect: teapot\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await.unwrap();
+    assert!(buf.starts_with(b"HTTP/1.1 417"));
+}
+
+#[cfg(all(test, feature = "http1"))]
+#[tokio::test]
+async fn header_read_timeout_drops_slow_client() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let service = Builder::new()
+        .header_read_timeout(Duration::from_millis(100))
+        .build(router);
+    let (ready, bound) = tokio::sync::oneshot::channel();
+    tokio::spawn(service.run_with_ready(([127, 0, 0, 1], 0).into(), ready));
+
+    let mut stream = tokio::net::TcpStream::connect(bound.await.unwrap())
+        .await
+        .unwrap();
+
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: ").await.unwrap();
+
+    let mut buf = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+        .await
+        .expect("connection wasn't closed");
+    assert!(read.is_err() || buf.is_empty() || buf.starts_with(b"HTTP/1.1 408"));
+}
+
+#[cfg(all(test, feature = "http1"))]
+#[tokio::test]
+async fn max_connections_closes_excess() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn get(stream: &mut TcpStream) -> Vec<u8> {
+        let req = b"GET /alive HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        if stream.write_all(req).await.is_err() {
+            return Vec::new();
+        }
+        let mut buf = vec![0; 1024];
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .expect("no response")
+            .unwrap_or(0);
+        buf.truncate(read);
+        buf
+    }
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let (_handle, addr) = Builder::new()
+        .max_connections(2)
+        .build(router)
+        .spawn(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    let mut second = TcpStream::connect(addr).await.unwrap();
+    assert!(get(&mut first).await.starts_with(b"HTTP/1.1 200"));
+    assert!(get(&mut second).await.starts_with(b"HTTP/1.1 200"));
+
+    for _ in 0..5 {
+        let mut excess = TcpStream::connect(addr).await.unwrap();
+        assert!(get(&mut excess).await.is_empty());
+    }
+
+    drop(first);
+    for _ in 0..50 {
+        let mut retry = TcpStream::connect(addr).await.unwrap();
+        if get(&mut retry).await.starts_with(b"HTTP/1.1 200") {
+            assert!(get(&mut second).await.starts_with(b"HTTP/1.1 200"));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("connection slot wasn't released");
+}
+
+#[cfg(all(test, feature = "http1"))]
+#[tokio::test]
+async fn response_write_timeout_drops_slow_reader() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const LEN: usize = 64 << 20;
+
+    fn large(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async { Ok(Response::new("x".repeat(LEN))) })
+    }
+
+    let router = Router::from_handler(Arc::new(()), large);
+    let (_handle, addr) = Builder::new()
+        .response_write_timeout(Duration::from_millis(100))
+        .build(router)
+        .spawn(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut buf = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+        .await
+        .expect("connection wasn't closed");
+    assert!(read.is_err() || buf.len() < LEN);
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+#[tokio::test]
+async fn min_body_rate_drops_slow_uploads() {
+    fn echo_body(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async move {
+            let mut resp = Response::new(String::new());
+            match req.into_body() {
+                Ok(body) => *resp.body_mut() = body.to_owned(),
+                Err(err) => *resp.status_mut() = crate::error::Error::status(&*err),
+            }
+            Ok(resp)
+        })
+    }
+
+    let router = Router::from_handler(Arc::new(()), echo_body);
+    let mut service = Builder::new().min_body_rate(100).build(router);
+
+    let req = Request::post("/")
+        .header(header::CONTENT_LENGTH, 5)
+        .body(Body::from("quick"))
+        .unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "quick");
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        for _ in 0..300 {
+            if sender.send_data(Bytes::from_static(b"x")).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+    let req = Request::post("/")
+        .header(header::CONTENT_LENGTH, 300)
+        .body(body)
+        .unwrap();
+    assert_eq!(
+        call_service(&mut service, req).await.status(),
+        StatusCode::REQUEST_TIMEOUT
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn json_number_precision_rounds_floats() {
+    let router =
+        Router::new(Arc::new(())).route(SupportedMethod::Get, "/price", |_app, ()| async {
+            Ok::<_, BaseError>(serde_json::json!({
+                "price": 1.005,
+                "rates": [1.2345, 2.0],
+                "count": 7,
+            }))
+        });
+    let mut service = Builder::new().json_number_precision(2).build(router);
+
+    let req = Request::get("/price").body(Body::empty()).unwrap();
+    let body: serde_json::Value =
+        serde_json::from_str(call_service(&mut service, req).await.body()).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"price": 1.01, "rates": [1.23, 2.0], "count": 7})
+    );
+}
+
+#[test]
+fn round_decimal_uses_decimal_digits() {
+    assert_eq!(round_decimal(1.005, 2), 1.01);
+    assert_eq!(round_decimal(-1.005, 2), -1.01);
+    assert_eq!(round_decimal(2.675, 2), 2.68);
+    assert_eq!(round_decimal(1.2345, 2), 1.23);
+    assert_eq!(round_decimal(9.995, 2), 10.0);
+    assert_eq!(round_decimal(0.5, 0), 1.0);
+    assert_eq!(round_decimal(1.5, 3), 1.5);
+}
+
+#[cfg(all(test, feature = "tower"))]
+#[tokio::test]
+async fn tower_layer_wraps_service() {
+    use tower::{Layer, ServiceExt};
+
+    let tagged = tower::util::MapResponseLayer::new(|mut resp: Response<OutBuffer>| {
+        resp.headers_mut()
+            .insert("x-layer", header::HeaderValue::from_static("tower"));
+        resp
+    });
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let service = tagged.clone().layer(Builder::new().build(router));
+
+    let req = Request::get("/towered").body(Body::empty()).unwrap();
+    let resp = service.oneshot(req).await.unwrap();
+    assert_eq!(resp.headers()["x-layer"], "tower");
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "/towered");
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let service = Builder::new().build(router);
+    let (server, addr) = service.bind(([127, 0, 0, 1], 0).into()).unwrap();
+    tokio::spawn(service.serve_with_layer(server, tagged));
+
+    let uri: http::Uri = format!("http://{}/served", addr).parse().unwrap();
+    let resp = hyper::Client::new().get(uri).await.unwrap();
+    assert_eq!(resp.headers()["x-layer"], "tower");
+}
+
+#[cfg(all(test, feature = "rustls", feature = "http1"))]
+#[tokio::test]
+async fn tls_serves_after_failed_handshake() {
+    use std::convert::TryFrom;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::pki_types::{PrivatePkcs8KeyDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let key = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+    let server_config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.cert.der().clone()], key.into())
+        .unwrap();
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let service = Builder::new()
+        .tls_handshake_timeout(Duration::from_millis(100))
+        .build(router);
+    let incoming = service.listen(([127, 0, 0, 1], 0).into()).unwrap();
+    let addr = incoming.local_addr();
+    tokio::spawn(service.serve_tls(incoming, server_config));
+
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.cert.der().clone()).unwrap();
+    let mut client_config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    client_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+
+    let mut plain = tokio::net::TcpStream::connect(addr).await.unwrap();
+    plain.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+
+    // Never starting the handshake gets the connection closed after the timeout
+    let mut stalled = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let read = tokio::time::timeout(Duration::from_secs(5), stalled.read(&mut [0; 16])).await;
+    assert!(matches!(read, Ok(Ok(0)) | Ok(Err(_))), "{:?}", read);
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let name = ServerName::try_from("localhost").unwrap();
+    let tls = connector.connect(name, stream).await.unwrap();
+    assert_eq!(tls.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+
+    let (mut sender, conn) = hyper::client::conn::handshake(tls).await.unwrap();
+    tokio::spawn(conn);
+    let req = Request::get("/secure").body(Body::empty()).unwrap();
+    let resp = sender.send_request(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "/secure");
+}
+
+#[cfg(all(test, feature = "rustls", feature = "http1", feature = "http2"))]
+#[test]
+fn tls_alpn_follows_http2_only() {
+    let config = Builder::new().config;
+    assert_eq!(config.alpn_protocols(), [&b"h2"[..], b"http/1.1"]);
+
+    let config = Builder::new().http2_only(true).config;
+    assert_eq!(config.alpn_protocols(), [&b"h2"[..]]);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn https_redirect_forwarded_http() {
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let mut service = Builder::new()
+        .trust_proxy(1)
+        .enforce_https_redirect()
+        .build(router);
+
+    let req = Request::get("/users?page=2")
+        .header(header::HOST, "example.com")
+        .header("x-forwarded-proto", "http")
+        .body(Body::empty())
+        .unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        resp.headers()[header::LOCATION],
+        "https://example.com/users?page=2"
+    );
+
+    let req = Request::get("/users")
+        .header(header::HOST, "example.com")
+        .header("x-forwarded-proto", "https")
+        .body(Body::empty())
+        .unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "/users");
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let mut untrusted = Builder::new().enforce_https_redirect().build(router);
+    let req = Request::get("/users")
+        .header(header::HOST, "example.com")
+        .header("x-forwarded-proto", "http")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(
+        call_service(&mut untrusted, req).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[cfg(all(test, debug_assertions))]
+#[tokio::test]
+async fn max_response_length_strict() {
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let mut service = Builder::new().max_response_length(8, true).build(router);
+
+    let req = Request::get("/short").body(Body::empty()).unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "/short");
+
+    let req = Request::get("/much/too/long").body(Body::empty()).unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(
+        resp.body().contains("longer than the limit 8"),
+        "{}",
+        resp.body()
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+#[should_panic(expected = "response of GET /inner doesn't match its schema")]
+async fn validate_responses_flags_missing_field() {
+    use crate::schema::registry_fixture::Inner;
+
+    let router = Router::new(Arc::new(()))
+        .route(SupportedMethod::Get, "/inner", |_app, ()| async {
+            Ok::<_, BaseError>(Inner { value: 42 })
+        })
+        .with(|_| {
+            |_app: Arc<()>, _req: Request<Result<&str, Box<BaseError>>>| -> BoxFuture<'_, _> {
+                Box::pin(async { Ok(Response::new("{}".to_owned())) })
+            }
+        });
+    let mut service = Builder::new().validate_responses().build(router);
+
+    let req = Request::get("/inner").body(Body::empty()).unwrap();
+    call_service(&mut service, req).await;
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+#[tokio::test]
+async fn shutdown_timeout_aborts_hung_requests() {
+    fn hang(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(futures_util::future::pending())
+    }
+
+    let service = Builder::new()
+        .shutdown_timeout(Duration::from_millis(100))
+        .build(Router::from_handler(Arc::new(()), hang));
+    let (server, addr) = service.bind(([127, 0, 0, 1], 0).into()).unwrap();
+    let server = tokio::spawn(
+        service.serve_with_shutdown(server, tokio::time::sleep(Duration::from_millis(200))),
+    );
+
+    tokio::spawn(async move {
+        let uri: http::Uri = format!("http://{}/", addr).parse().unwrap();
+        let _ = hyper::Client::new().get(uri).await;
+    });
+
+    let res = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server didn't stop")
+        .unwrap();
+    assert!(matches!(res, Err(RunError::ShutdownTimeout)));
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+#[tokio::test]
+async fn binding_twice_reports_bind_error() {
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let (handle, addr) = Service::new(router.clone())
+        .spawn(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    match Service::new(router).run(addr).await {
+        Err(RunError::Bind { addr: failed, .. }) => assert_eq!(failed, addr),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    handle.abort();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn extensions_reach_the_handler() {
+    #[derive(Debug, Clone, Copy)]
+    struct Tenant(u32);
+    #[derive(Debug, Clone, Copy)]
+    struct RequestId(u64);
+
+    fn echo_ext(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let tenant = crate::router::ext::<Tenant, _>(&req).map(|t| t.0);
+        let id = crate::router::ext::<RequestId, _>(&req).map(|id| id.0);
+        Box::pin(async move { Ok(Response::new(format!("{:?} {:?}", tenant, id))) })
+    }
+
+    let router = Router::from_handler(Arc::new(()), echo_ext).map_request(|mut req| {
+        req.extensions_mut().insert(RequestId(7));
+        req
+    });
+    let mut service = Service::new(router);
+
+    let mut req = Request::post("/").body(Body::from("body")).unwrap();
+    req.extensions_mut().insert(Tenant(3));
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.body(), "Some(3) Some(7)");
+}
+
+#[cfg(all(test, feature = "gzip", feature = "brotli"))]
+#[tokio::test]
+async fn compression_round_trip() {
+    use std::io::Read;
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let mut service = Builder::new()
+        .compression(Algorithm::Gzip, 6)
+        .compression(Algorithm::Brotli, 5)
+        .build(router);
+
+    let path = format!("/{}", "compressible/".repeat(32));
+    let call = |service: &mut Service<(), _>, accept_encoding: &'static str| {
+        let req = Request::get(&*path)
+            .header(header::ACCEPT_ENCODING, accept_encoding)
+            .body(Body::empty())
+            .unwrap();
+        let resp = HyperService::call(service, req);
+        async move {
+            let (parts, body) = resp.await.unwrap().into_parts();
+            let body = hyper::body::to_bytes(body).await.unwrap();
+            (parts.headers, body)
+        }
+    };
+
+    let (headers, body) = call(&mut service, "gzip;q=0.5, br").await;
+    assert_eq!(headers[header::CONTENT_ENCODING], "br");
+    assert_eq!(headers[header::VARY], "accept-encoding");
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&body[..], 4096)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, path);
+
+    let (headers, body) = call(&mut service, "br;q=0.1, gzip").await;
+    assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, path);
+
+    let (headers, body) = call(&mut service, "identity").await;
+    assert!(!headers.contains_key(header::CONTENT_ENCODING));
+    assert_eq!(body, path);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn normalize_path_modes() {
+    async fn get(normalization: PathNormalization, path: &str) -> Response<String> {
+        let router =
+            Router::new(Arc::new(())).route(SupportedMethod::Get, "/users", |_, ()| async {
+                Ok::<_, BaseError>("users".to_owned())
+            });
+        let mut service = Builder::new().normalize_path(normalization).build(router);
+        let req = Request::get(path).body(Body::empty()).unwrap();
+        call_service(&mut service, req).await
+    }
+
+    let resp = get(PathNormalization::Off, "/users/").await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    for path in &["/users", "/users/", "//users", "/users//?page=2"] {
+        let resp = get(PathNormalization::Strip, path).await;
+        assert_eq!(resp.body(), "users", "{}", path);
+    }
+
+    let resp = get(PathNormalization::Append, "/users").await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = get(PathNormalization::Redirect, "/users//?page=2").await;
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers()[header::LOCATION], "/users?page=2");
+    let resp = get(PathNormalization::Redirect, "/users").await;
+    assert_eq!(resp.body(), "users");
+
+    let router = Router::from_handler(Arc::new(()), crate::router::echo_path);
+    let mut service = Builder::new()
+        .normalize_path(PathNormalization::Append)
+        .build(router);
+    let req = Request::get("//users//1").body(Body::empty()).unwrap();
+    assert_eq!(call_service(&mut service, req).await.body(), "/users/1/");
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+#[tokio::test]
+async fn concurrency_limit_applies_backpressure() {
+    fn slow(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(Response::new("done".into()))
+        })
+    }
+
+    let router = Router::from_handler(Arc::new(()), slow);
+    let mut first = Builder::new().concurrency_limit(1).build(router);
+    let mut second = first.clone();
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut poll_ready = |service: &mut Service<(), _>| {
+        HyperService::<Request<Body>>::poll_ready(service, &mut cx).is_ready()
+    };
+
+    // Ready connections which don't send the request yet don't take the permit.
+    assert!(poll_ready(&mut first));
+    assert!(poll_ready(&mut second));
+
+    let resp = HyperService::call(&mut first, Request::get("/").body(Body::empty()).unwrap());
+    let resp = tokio::spawn(resp);
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(!poll_ready(&mut second));
+
+    resp.await.unwrap().unwrap();
+    assert!(poll_ready(&mut second));
+    assert!(poll_ready(&mut first));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn request_concurrency_per_ip_rejects_busy_client() {
+    fn slow(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(Response::new("done".into()))
+        })
+    }
+
+    let router = Router::from_handler(Arc::new(()), slow);
+    let service = Builder::new().request_concurrency_per_ip(1).build(router);
+    let from = |ip: [u8; 4]| {
+        let mut service = service.clone();
+        service.remote_addr = Some((ip, 4000).into());
+        async move {
+            let req = Request::get("/").body(Body::empty()).unwrap();
+            call_service(&mut service, req).await.status()
+        }
+    };
+
+    let (first, second, other) = futures_util::join!(
+        from([10, 0, 0, 1]),
+        from([10, 0, 0, 1]),
+        from([10, 0, 0, 2])
+    );
+    assert_eq!(first, StatusCode::OK);
+    assert_eq!(second, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(other, StatusCode::OK);
+
+    let limit = service.config.per_ip_concurrency.as_ref().unwrap();
+    assert!(limit.in_flight.lock().unwrap().is_empty());
+    assert_eq!(from([10, 0, 0, 1]).await, StatusCode::OK);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn request_concurrency_per_ip_limits_unknown_clients() {
+    fn slow(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(Response::new("done".into()))
+        })
+    }
+
+    let router = Router::from_handler(Arc::new(()), slow);
+    let service = Builder::new()
+        .trust_proxy(1)
+        .request_concurrency_per_ip(1)
+        .build(router);
+    let call = |peer: Option<[u8; 4]>, forwarded: &'static str| {
+        let mut service = service.clone();
+        service.remote_addr = peer.map(|ip| (ip, 4000).into());
+        async move {
+            let req = Request::get("/")
+                .header("x-forwarded-for", forwarded)
+                .body(Body::empty())
+                .unwrap();
+            call_service(&mut service, req).await.status()
+        }
+    };
+
+    // Malformed hops fall back to the peer instead of skipping the limit
+    let (first, second) = futures_util::join!(
+        call(Some([10, 0, 0, 1]), "not-an-ip"),
+        call(Some([10, 0, 0, 1]), "also, garbage")
+    );
+    assert_eq!(first, StatusCode::OK);
+    assert_eq!(second, StatusCode::TOO_MANY_REQUESTS);
+
+    // Without the peer address either, the requests share a single bucket
+    let (first, second) = futures_util::join!(call(None, "not-an-ip"), call(None, "garbage"));
+    assert_eq!(first, StatusCode::OK);
+    assert_eq!(second, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn error_localizer_follows_accept_language() {
+    fn conflict(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let err = DynError::new(StatusCode::CONFLICT, "already exists");
+        Box::pin(async move { Ok(crate::error::error_response(&BaseError::Other(err))) })
+    }
+
+    let router = Router::from_handler(Arc::new(()), conflict);
+    let mut service = Builder::new()
+        .error_localizer(|err, lang| match (err, lang.primary()) {
+            (BaseError::Other(err), "ko") if err.status == StatusCode::CONFLICT => {
+                Some("이미 존재합니다".into())
+            }
+            _ => None,
+        })
+        .build(router);
+
+    let request = |accept_language: &str| {
+        Request::get("/")
+            .header(header::ACCEPT_LANGUAGE, accept_language)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let resp = call_service(&mut service, request("fr, ko-KR;q=0.9")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(resp.headers()[header::CONTENT_LANGUAGE], "ko-kr");
+    let body: serde_json::Value = serde_json::from_str(resp.body()).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"Other": {"status": 409, "error": "이미 존재합니다"}})
+    );
+
+    let resp = call_service(&mut service, request("fr")).await;
+    assert!(!resp.headers().contains_key(header::CONTENT_LANGUAGE));
+    let body: serde_json::Value = serde_json::from_str(resp.body()).unwrap();
+    assert_eq!(body["Other"]["error"], "already exists");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn default_response_headers_are_scoped_per_router() {
+    fn cached(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let mut resp = Response::new(String::new());
+        resp.headers_mut()
+            .insert(header::CACHE_CONTROL, "no-store".parse().unwrap());
+        Box::pin(async move { Ok(resp) })
+    }
+
+    async fn noop(_app: Arc<()>, _body: ()) -> Result<(), String> {
+        Ok(())
+    }
+
+    let mut service_headers = HeaderMap::new();
+    service_headers.insert(header::CACHE_CONTROL, "private".parse().unwrap());
+    service_headers.insert("x-frame-options", "DENY".parse().unwrap());
+    let mut static_headers = HeaderMap::new();
+    static_headers.insert(header::CACHE_CONTROL, "max-age=3600".parse().unwrap());
+
+    let app = Arc::new(());
+    let statics = Router::new(Arc::clone(&app))
+        .route(SupportedMethod::Get, "/static/app.js", noop)
+        .route(SupportedMethod::Get, "/static/app.css", noop)
+        .with_default_response_headers(static_headers);
+    let router = Router::from_handler(app, cached)
+        .route(SupportedMethod::Get, "/api", noop)
+        .merge(statics);
+    let mut service = Builder::new()
+        .default_response_headers(service_headers)
+        .build(router);
+
+    let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+    let resp = call_service(&mut service, get("/static/app.js")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "max-age=3600");
+    assert_eq!(resp.headers()["x-frame-options"], "DENY");
+
+    let resp = call_service(&mut service, get("/api")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "private");
+    assert_eq!(resp.headers()["x-frame-options"], "DENY");
+
+    let resp = call_service(&mut service, get("/other")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn default_cache_control_overrides_default_headers_on_errors() {
+    async fn missing(_app: Arc<()>, _body: ()) -> Result<(), BaseError> {
+        Err(BaseError::NotFound)
+    }
+
+    async fn noop(_app: Arc<()>, _body: ()) -> Result<(), String> {
+        Ok(())
+    }
+
+    let mut service_headers = HeaderMap::new();
+    service_headers.insert(header::CACHE_CONTROL, "private".parse().unwrap());
+    let mut static_headers = HeaderMap::new();
+    static_headers.insert(header::CACHE_CONTROL, "max-age=3600".parse().unwrap());
+
+    let app = Arc::new(());
+    let statics = Router::new(Arc::clone(&app))
+        .route(SupportedMethod::Get, "/static/app.js", noop)
+        .route(SupportedMethod::Get, "/static/gone.js", missing)
+        .with_default_response_headers(static_headers);
+    let router = Router::new(app)
+        .route(SupportedMethod::Get, "/api", noop)
+        .merge(statics);
+    let mut service = Builder::new()
+        .default_response_headers(service_headers)
+        .default_cache_control(HeaderValue::from_static("private, max-age=10"))
+        .build(router);
+
+    let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+    let resp = call_service(&mut service, get("/static/app.js")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "max-age=3600");
+
+    let resp = call_service(&mut service, get("/static/gone.js")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+
+    let resp = call_service(&mut service, get("/api")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "private");
+
+    let resp = call_service(&mut service, get("/missing")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn default_headers_reach_errors_without_cache_control() {
+    async fn missing(_app: Arc<()>, _body: ()) -> Result<(), BaseError> {
+        Err(BaseError::NotFound)
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CACHE_CONTROL, "public, max-age=60".parse().unwrap());
+
+    let app = Arc::new(());
+    let statics = Router::new(Arc::clone(&app))
+        .route(SupportedMethod::Get, "/static/gone.js", missing)
+        .with_default_response_headers(headers.clone());
+    let router = Router::new(app).merge(statics);
+
+    let req = Request::get("/static/gone.js").body(Ok("")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "public, max-age=60");
+
+    let mut service = Builder::new()
+        .default_response_headers(headers)
+        .build(router);
+
+    let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+    let resp = call_service(&mut service, get("/static/gone.js")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "public, max-age=60");
+
+    let resp = call_service(&mut service, get("/missing")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "public, max-age=60");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn default_cache_control_by_status() {
+    fn handler(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let mut resp = Response::new(String::new());
+        match req.uri().path() {
+            "/ok" => {}
+            "/cached" => {
+                resp.headers_mut()
+                    .insert(header::CACHE_CONTROL, "max-age=60".parse().unwrap());
+            }
+            "/cached-error" => {
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+                resp.headers_mut()
+                    .insert(header::CACHE_CONTROL, "max-age=60".parse().unwrap());
+            }
+            "/moved" => *resp.status_mut() = StatusCode::MOVED_PERMANENTLY,
+            "/fail" => *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR,
+            _ => *resp.status_mut() = StatusCode::NOT_FOUND,
+        }
+        Box::pin(async move { Ok(resp) })
+    }
+
+    let router = Router::from_handler(Arc::new(()), handler);
+    let mut service = Builder::new()
+        .enable_expect_continue()
+        .default_cache_control(HeaderValue::from_static("private, max-age=10"))
+        .build(router);
+
+    let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+    let resp = call_service(&mut service, get("/ok")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "private, max-age=10");
+
+    let resp = call_service(&mut service, get("/missing")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+
+    let resp = call_service(&mut service, get("/fail")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+
+    let resp = call_service(&mut service, get("/cached")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "max-age=60");
+
+    let resp = call_service(&mut service, get("/cached-error")).await;
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "max-age=60");
+
+    let resp = call_service(&mut service, get("/moved")).await;
+    assert!(!resp.headers().contains_key(header::CACHE_CONTROL));
+
+    // Rejected before reaching the handler.
+    let req = Request::get("/ok")
+        .header(header::EXPECT, "something-else")
+        .body(Body::empty())
+        .unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::EXPECTATION_FAILED);
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn observers_see_each_request_once() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn slow(
+        _app: Arc<()>,
+        req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let found = req.uri().path() == "/found";
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let mut resp = Response::new(String::new());
+            if !found {
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+            }
+            Ok(resp)
+        })
+    }
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let responses = Arc::new(Mutex::new(vec![]));
+    let router = Router::from_handler(Arc::new(()), slow);
+    let mut service = Builder::new()
+        .on_request({
+            let requests = Arc::clone(&requests);
+            move |_| {
+                requests.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .on_response({
+            let responses = Arc::clone(&responses);
+            move |parts, status, latency| {
+                let path = parts.uri.path().to_owned();
+                responses.lock().unwrap().push((path, status, latency));
+            }
+        })
+        .build(router);
+
+    for path in &["/found", "/missing"] {
+        let req = Request::get(*path).body(Body::empty()).unwrap();
+        call_service(&mut service, req).await;
+    }
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    let responses = responses.lock().unwrap();
+    let observed: Vec<_> = responses
+        .iter()
+        .map(|(path, status, _)| (path.as_str(), *status))
+        .collect();
+    assert_eq!(
+        observed,
+        [
+            ("/found", StatusCode::OK),
+            ("/missing", StatusCode::NOT_FOUND)
+        ]
+    );
+    assert!(responses
+        .iter()
+        .all(|(_, _, latency)| *latency >= Duration::from_millis(10)));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn max_uri_length_rejects_long_target() {
+    let router = Router::new(Arc::new(()));
+    let mut service = Builder::new().max_uri_length(16).build(router);
+
+    let req = Request::get("/short").body(Body::empty()).unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = Request::get("/much/longer/than/limit")
+        .body(Body::empty())
+        .unwrap();
+    let resp = call_service(&mut service, req).await;
+    assert_eq!(resp.status(), StatusCode::URI_TOO_LONG);
+    assert_eq!(resp.body(), "\"UriTooLong\"");
+}
+
+impl OutBuffer {
+    pub fn empty() -> Self {
+        String::new().into()
+    }
+
+    /// Streams the `chunks` after the body, flushing each of them as it arrives.
+    pub fn with_chunks(mut self, chunks: impl Stream<Item = Vec<u8>> + Send + 'static) -> Self {
+        self.chunks = Some(Chunks(Arc::new(Mutex::new(chunks.boxed()))));
+        self
+    }
+
+    /// Sends the `trailers` after the body.
+    pub fn with_trailers(mut self, trailers: HeaderMap) -> Self {
+        self.trailers = Some(trailers);
+        self
+    }
+}
+
+impl From<String> for OutBuffer {
+    fn from(s: String) -> Self {
+        s.into_bytes().into()
+    }
+}
+
+impl From<Vec<u8>> for OutBuffer {
+    /// The empty buffer ends the stream immediately, without yielding any chunk.
+    fn from(v: Vec<u8>) -> Self {
+        Self {
+            inner: if v.is_empty() { None } else { Some(v) },
+            chunks: None,
+            trailers: None,
+        }
+    }
+}
+
+impl hyper::body::HttpBody for OutBuffer {
+    type Data = Cursor<Vec<u8>>;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if let Some(v) = self.inner.take() {
+            return Poll::Ready(Some(Ok(Cursor::new(v))));
+        }
+        let chunk = match &self.chunks {
+            Some(Chunks(stream)) => {
+                futures_util::ready!(stream.lock().unwrap().poll_next_unpin(cx))
+            }
+            None => return Poll::Ready(None),
+        };
+        if chunk.is_none() {
+            self.chunks = None;
+        }
+        Poll::Ready(chunk.map(|v| Ok(Cursor::new(v))))
     }
 
     fn poll_trailers(
-        self: Pin<&mut Self>,
+        mut self: Pin<&mut Self>,
         _cx: &mut Context<'_>,
     ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
-        Poll::Ready(Ok(None))
+        Poll::Ready(Ok(self.trailers.take()))
     }
 
     fn is_end_stream(&self) -> bool {
-        self.inner.is_none()
+        self.inner.is_none() && self.chunks.is_none() && self.trailers.is_none()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        let len = self.inner.as_ref().map_or(0, Vec::len) as u64;
+        if self.chunks.is_some() {
+            let mut hint = hyper::body::SizeHint::new();
+            hint.set_lower(len);
+            hint
+        } else {
+            hyper::body::SizeHint::with_exact(len)
+        }
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn out_buffer_ends_stream() {
+    use hyper::body::HttpBody;
+
+    let mut empty = OutBuffer::empty();
+    assert!(empty.is_end_stream());
+    assert_eq!(empty.size_hint().exact(), Some(0));
+    assert!(empty.data().await.is_none());
+
+    let mut body = OutBuffer::from("hello".to_owned());
+    assert!(!body.is_end_stream());
+    assert_eq!(body.size_hint().exact(), Some(5));
+    assert_eq!(body.data().await.unwrap().unwrap().into_inner(), b"hello");
+    assert!(body.is_end_stream());
+    assert!(body.data().await.is_none());
+    assert!(body.trailers().await.unwrap().is_none());
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", header::HeaderValue::from_static("0"));
+    let mut body = OutBuffer::empty().with_trailers(trailers.clone());
+    assert!(!body.is_end_stream());
+    assert!(body.data().await.is_none());
+    assert_eq!(body.trailers().await.unwrap(), Some(trailers));
+    assert!(body.is_end_stream());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn event_stream_frames_events() {
+    use crate::sse::Event;
+
+    fn events(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let events = futures_util::stream::iter(vec![
+            Event::new("first").event("greet").id("1"),
+            Event::new("second\nline"),
+        ]);
+        Box::pin(async move { Ok(EventStream::new(events).into_response()) })
+    }
+
+    let router = Router::from_handler(Arc::new(()), events);
+    let mut service = Builder::new().build(router);
+
+    let req = Request::get("/").body(Body::empty()).unwrap();
+    let resp = HyperService::call(&mut service, req).await.unwrap();
+    assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/event-stream");
+    assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-cache");
+    assert_eq!(resp.headers()["x-accel-buffering"], "no");
+    assert!(!resp.headers().contains_key(header::CONTENT_LENGTH));
+    assert_eq!(hyper::body::HttpBody::size_hint(resp.body()).exact(), None);
+
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(
+        body,
+        "event: greet\nid: 1\ndata: first\n\ndata: second\ndata: line\n\n"
+    );
+}
+
+#[cfg(all(test, feature = "http2"))]
+#[tokio::test]
+async fn trailers_reach_h2_client() {
+    use hyper::body::HttpBody;
+
+    fn with_trailers(
+        _app: Arc<()>,
+        _req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        Box::pin(async {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", header::HeaderValue::from_static("0"));
+            let mut resp = Response::new("payload".to_owned());
+            resp.extensions_mut().insert(Trailers(trailers));
+            Ok(resp)
+        })
     }
+
+    let router = Router::from_handler(Arc::new(()), with_trailers);
+    let (_handle, addr) = Builder::new()
+        .http2_only(true)
+        .build(router)
+        .spawn(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<Body>();
+    let uri: http::Uri = format!("http://{}/", addr).parse().unwrap();
+    let mut body = client.get(uri).await.unwrap().into_body();
+
+    assert_eq!(body.data().await.unwrap().unwrap(), "payload");
+    assert!(body.data().await.is_none());
+    let trailers = body.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["grpc-status"], "0");
 }
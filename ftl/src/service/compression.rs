@@ -0,0 +1,147 @@
+//! Response compression negotiated with the `Accept-Encoding` header.
+
+use std::io::Write;
+
+use http::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+use http::{HeaderMap, Response, StatusCode};
+
+use crate::BoxError;
+
+/// Content coding to compress the response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Algorithm {
+    /// `gzip` coding, with the level from 0 to 9.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// `br` coding, with the quality from 0 to 11.
+    #[cfg(feature = "brotli")]
+    Brotli,
+}
+
+impl Algorithm {
+    fn coding(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Algorithm::Gzip => "gzip",
+            #[cfg(feature = "brotli")]
+            Algorithm::Brotli => "br",
+        }
+    }
+
+    fn compress(self, level: u32, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Algorithm::Gzip => {
+                let level = flate2::Compression::new(level.min(9));
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "brotli")]
+            Algorithm::Brotli => {
+                let mut encoder =
+                    brotli::CompressorWriter::new(Vec::new(), 4096, level.min(11), 22);
+                encoder.write_all(body)?;
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+/// Picks the enabled algorithm with the highest q-value from the `Accept-Encoding`.
+///
+/// Ties are broken by the order the algorithms are enabled.
+fn negotiate(enabled: &[(Algorithm, u32)], headers: &HeaderMap) -> Option<(Algorithm, u32)> {
+    let accepted: Vec<(&str, f32)> = headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| {
+            let mut params = item.split(';');
+            let coding = params.next()?.trim();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .next()
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            Some((coding, q))
+        })
+        .collect();
+
+    let quality = |coding: &str| {
+        accepted
+            .iter()
+            .find(|(accepted, _)| accepted.eq_ignore_ascii_case(coding))
+            .or_else(|| accepted.iter().find(|(accepted, _)| *accepted == "*"))
+            .map_or(0.0, |&(_, q)| q)
+    };
+
+    let mut best = None;
+    // `Option::is_none_or` needs Rust 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    for &(algorithm, level) in enabled {
+        let q = quality(algorithm.coding());
+        if q > 0.0 && best.map_or(true, |(_, _, best_q)| q > best_q) {
+            best = Some((algorithm, level, q));
+        }
+    }
+
+    best.map(|(algorithm, level, _)| (algorithm, level))
+}
+
+pub(super) fn compress(
+    enabled: &[(Algorithm, u32)],
+    req_headers: &HeaderMap,
+    resp: Response<String>,
+) -> Result<Response<Vec<u8>>, BoxError> {
+    let (mut parts, body) = resp.into_parts();
+    let body = body.into_bytes();
+
+    if enabled.is_empty()
+        || body.is_empty()
+        || parts.status == StatusCode::NO_CONTENT
+        || parts.status == StatusCode::NOT_MODIFIED
+        || parts.headers.contains_key(CONTENT_ENCODING)
+    {
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+
+    let (algorithm, level) = match negotiate(enabled, req_headers) {
+        Some(picked) => picked,
+        None => return Ok(Response::from_parts(parts, body)),
+    };
+
+    let body = algorithm.compress(level, &body)?;
+    parts.headers.insert(
+        CONTENT_ENCODING,
+        HeaderValue::from_static(algorithm.coding()),
+    );
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+
+    Ok(Response::from_parts(parts, body))
+}
+
+#[cfg(all(feature = "gzip", feature = "brotli"))]
+#[test]
+fn negotiate_by_q_values() {
+    let enabled = [(Algorithm::Gzip, 6), (Algorithm::Brotli, 4)];
+    let headers = |value: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    };
+
+    let pick = |value| negotiate(&enabled, &headers(value)).map(|(algorithm, _)| algorithm);
+    assert_eq!(pick("gzip, br"), Some(Algorithm::Gzip));
+    assert_eq!(pick("gzip;q=0.5, br"), Some(Algorithm::Brotli));
+    assert_eq!(pick("br;q=0.2, gzip;q=0.8"), Some(Algorithm::Gzip));
+    assert_eq!(pick("*;q=0.1, gzip;q=0"), Some(Algorithm::Brotli));
+    assert_eq!(pick("identity"), None);
+    assert_eq!(negotiate(&enabled, &HeaderMap::new()), None);
+}
@@ -0,0 +1,177 @@
+//! Listener which caps the number of live connections
+//! and times out the stalled writes of them.
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::future::FutureExt;
+use hyper::server::accept::Accept;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+use super::{Config, RemoteAddr};
+
+/// Accepts the connections from the `AddrIncoming`, closing them right away
+/// while the `max_connections` are already open.
+#[derive(Debug)]
+pub(super) struct Incoming {
+    inner: AddrIncoming,
+    max_connections: Option<usize>,
+    write_timeout: Option<Duration>,
+    live: Arc<AtomicUsize>,
+}
+
+/// Accepted connection which is counted as live until dropped.
+#[derive(Debug)]
+pub struct Conn {
+    stream: AddrStream,
+    live: Arc<AtomicUsize>,
+    write_timeout: Option<Duration>,
+    /// Started when the write becomes pending, and reset once it makes progress.
+    write_deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl Incoming {
+    pub(super) fn new(inner: AddrIncoming, config: &Config) -> Self {
+        Self {
+            inner,
+            max_connections: config.max_connections,
+            write_timeout: config.response_write_timeout,
+            live: Arc::default(),
+        }
+    }
+
+    pub(super) fn local_addr(&self) -> SocketAddr {
+        self.inner.local_addr()
+    }
+}
+
+impl Conn {
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.stream.remote_addr()
+    }
+
+    /// Fails the pending write once it's stalled for the `write_timeout`.
+    fn timeout_write<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let timeout = match (poll.is_pending(), self.write_timeout) {
+            (true, Some(timeout)) => timeout,
+            _ => {
+                self.write_deadline = None;
+                return poll;
+            }
+        };
+
+        let deadline = self
+            .write_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match deadline.poll_unpin(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "writing the response timed out",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Accept for Incoming {
+    type Conn = Conn;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Conn, io::Error>>> {
+        loop {
+            let stream = match Pin::new(&mut self.inner).poll_accept(cx) {
+                Poll::Ready(Some(Ok(stream))) => stream,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let live = self.live.fetch_add(1, Ordering::AcqRel);
+            let conn = Conn {
+                stream,
+                live: Arc::clone(&self.live),
+                write_timeout: self.write_timeout,
+                write_deadline: None,
+            };
+
+            if self.max_connections.is_some_and(|max| live >= max) {
+                tracing::debug!(
+                    "closing the connection from {}, the connection limit is reached",
+                    conn.remote_addr()
+                );
+                continue;
+            }
+
+            return Poll::Ready(Some(Ok(conn)));
+        }
+    }
+}
+
+impl Drop for Conn {
+    fn drop(&mut self) {
+        self.live.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl RemoteAddr for Conn {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(Conn::remote_addr(self))
+    }
+}
+
+impl AsyncRead for Conn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Conn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.stream).poll_write(cx, buf);
+        self.timeout_write(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.stream).poll_write_vectored(cx, bufs);
+        self.timeout_write(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.stream).poll_flush(cx);
+        self.timeout_write(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
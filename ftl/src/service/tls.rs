@@ -0,0 +1,97 @@
+//! Listener which accepts the TLS connections on top of the [`Incoming`].
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use hyper::server::accept::Accept;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use super::incoming::{Conn, Incoming};
+use super::RemoteAddr;
+
+/// Runs the handshakes of the accepted connections concurrently,
+/// yielding the ones which complete within the `handshake_timeout`.
+///
+/// Failed handshakes are only logged, so they never stop the server.
+pub(super) struct TlsIncoming {
+    inner: Option<Incoming>,
+    acceptor: TlsAcceptor,
+    handshake_timeout: Duration,
+    handshakes: FuturesUnordered<BoxFuture<'static, Option<TlsStream<Conn>>>>,
+}
+
+impl TlsIncoming {
+    pub(super) fn new(inner: Incoming, acceptor: TlsAcceptor, handshake_timeout: Duration) -> Self {
+        Self {
+            inner: Some(inner),
+            acceptor,
+            handshake_timeout,
+            handshakes: FuturesUnordered::new(),
+        }
+    }
+
+    fn handshake(&self, conn: Conn) -> BoxFuture<'static, Option<TlsStream<Conn>>> {
+        let remote_addr = conn.remote_addr();
+        let accept = self.acceptor.accept(conn);
+        let timeout = self.handshake_timeout;
+
+        Box::pin(async move {
+            match tokio::time::timeout(timeout, accept).await {
+                Ok(Ok(stream)) => Some(stream),
+                Ok(Err(err)) => {
+                    tracing::debug!("TLS handshake with {} failed: {}", remote_addr, err);
+                    None
+                }
+                Err(_) => {
+                    tracing::debug!("TLS handshake with {} timed out", remote_addr);
+                    None
+                }
+            }
+        })
+    }
+}
+
+impl Accept for TlsIncoming {
+    type Conn = TlsStream<Conn>;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, io::Error>>> {
+        while let Some(inner) = &mut self.inner {
+            match Pin::new(inner).poll_accept(cx) {
+                Poll::Ready(Some(Ok(conn))) => {
+                    let handshake = self.handshake(conn);
+                    self.handshakes.push(handshake);
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    tracing::debug!("failed to accept the connection: {}", err);
+                }
+                Poll::Ready(None) => self.inner = None,
+                Poll::Pending => break,
+            }
+        }
+
+        loop {
+            match self.handshakes.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some(stream))) => return Poll::Ready(Some(Ok(stream))),
+                Poll::Ready(Some(None)) => {}
+                Poll::Ready(None) if self.inner.is_none() => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl RemoteAddr for TlsStream<Conn> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(self.get_ref().0.remote_addr())
+    }
+}
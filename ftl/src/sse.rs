@@ -0,0 +1,169 @@
+//! Server-Sent Events responses.
+//!
+//! The handler returns the response made by the [`EventStream::into_response`],
+//! and the events are written to the connection as the stream yields them.
+//!
+//! ```ignore
+//! fn ticks(_app: Arc<App>, _req: Request<...>) -> BoxFuture<...> {
+//!     let events = futures_util::stream::iter(vec![
+//!         Event::new("1").event("tick"),
+//!         Event::new("2").event("tick"),
+//!     ]);
+//!
+//!     Box::pin(async move { Ok(EventStream::new(events).into_response()) })
+//! }
+//! ```
+
+use std::fmt;
+use std::sync::Mutex;
+
+use futures_core::Stream;
+use futures_util::stream::{BoxStream, StreamExt};
+use http::header::{self, HeaderValue};
+use http::Response;
+use serde::{Deserialize, Serialize};
+
+use crate::Schema;
+
+/// Content type of the event streams.
+pub const CONTENT_TYPE: &str = "text/event-stream";
+
+/// Single event within the event stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub struct Event {
+    /// Event type, `message` if omitted.
+    pub event: Option<String>,
+    /// Last event ID the client reports on reconnect.
+    pub id: Option<String>,
+    pub data: String,
+}
+
+impl Event {
+    pub fn new(data: impl Into<String>) -> Self {
+        Event {
+            event: None,
+            id: None,
+            data: data.into(),
+        }
+    }
+
+    /// Event with the `data` serialized as JSON.
+    pub fn json<T: Serialize>(data: &T) -> serde_json::Result<Self> {
+        serde_json::to_string(data).map(Self::new)
+    }
+
+    /// Sets the event type. Line breaks are removed.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    /// Sets the event ID. Line breaks are removed.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(single_line(id.into()));
+        self
+    }
+
+    /// Writes the event in the `text/event-stream` format,
+    /// with a `data:` line per each line of the data, ended by any of
+    /// the `\r\n`, `\r` or `\n` like the SSE parsers do.
+    pub fn encode(&self) -> String {
+        let mut buf = String::new();
+        if let Some(event) = &self.event {
+            buf.push_str("event: ");
+            buf.push_str(event);
+            buf.push('\n');
+        }
+        if let Some(id) = &self.id {
+            buf.push_str("id: ");
+            buf.push_str(id);
+            buf.push('\n');
+        }
+        let mut rest = self.data.as_str();
+        loop {
+            let end = rest.find(['\r', '\n']).unwrap_or(rest.len());
+            buf.push_str("data: ");
+            buf.push_str(&rest[..end]);
+            buf.push('\n');
+
+            if end == rest.len() {
+                break;
+            }
+            let newline = if rest[end..].starts_with("\r\n") {
+                2
+            } else {
+                1
+            };
+            rest = &rest[end + newline..];
+        }
+        buf.push('\n');
+        buf
+    }
+}
+
+fn single_line(s: String) -> String {
+    if s.contains(['\r', '\n']) {
+        s.replace(['\r', '\n'], "")
+    } else {
+        s
+    }
+}
+
+/// Response body made of the events.
+///
+/// Take the response from the [`into_response`](EventStream::into_response)
+/// and the service streams the events after the headers, unbuffered.
+// The `Mutex` makes it `Sync` to be stored within the extensions.
+pub struct EventStream(Mutex<BoxStream<'static, Event>>);
+
+impl EventStream {
+    pub fn new(events: impl Stream<Item = Event> + Send + 'static) -> Self {
+        EventStream(Mutex::new(events.boxed()))
+    }
+
+    /// Response with the `text/event-stream` headers and this stream in its extensions.
+    pub fn into_response(self) -> Response<String> {
+        let mut resp = Response::new(String::new());
+        let headers = resp.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        headers.insert("x-accel-buffering", HeaderValue::from_static("no"));
+        resp.extensions_mut().insert(self);
+        resp
+    }
+
+    /// Encoded events.
+    pub(crate) fn into_chunks(self) -> BoxStream<'static, Vec<u8>> {
+        let events = self.0.into_inner().unwrap();
+        events.map(|event| event.encode().into_bytes()).boxed()
+    }
+}
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStream").finish_non_exhaustive()
+    }
+}
+
+#[test]
+fn encode_event_lines() {
+    assert_eq!(Event::new("hello").encode(), "data: hello\n\n");
+    assert_eq!(
+        Event::new("a\r\nb\nc").event("up\ndate").id("7").encode(),
+        "event: update\nid: 7\ndata: a\ndata: b\ndata: c\n\n"
+    );
+    assert_eq!(Event::new("a\rid: 9").encode(), "data: a\ndata: id: 9\n\n");
+    assert_eq!(
+        Event::new("a\r\rb\r").encode(),
+        "data: a\ndata: \ndata: b\ndata: \n\n"
+    );
+    assert_eq!(
+        Event::json(&vec![1, 2]).unwrap().encode(),
+        "data: [1,2]\n\n"
+    );
+}
+
+#[test]
+fn parse_example_event() {
+    crate::schema::parse_example::<Event>();
+}
@@ -0,0 +1,1496 @@
+//! Helper types with the wire representation other than their serde default.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::{HeaderMap, StatusCode};
+use openapiv3 as oa;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+
+use crate::error::{Error, ErrorSchema};
+use crate::schema::{byte_schema, Schema};
+
+/// Value serialized as its string representation, using `Display` and `FromStr`.
+///
+/// Useful for 64 bits integer IDs which can't be represented precisely
+/// with the JavaScript's number type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AsString<T>(pub T);
+
+impl<T: fmt::Display> Serialize for AsString<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for AsString<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct AsStringVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for AsStringVisitor<T>
+        where
+            T: FromStr,
+            T::Err: fmt::Display,
+        {
+            type Value = AsString<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("string representation of the value")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map(AsString).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(AsStringVisitor(PhantomData))
+    }
+}
+
+impl<T> Schema for AsString<T>
+where
+    T: Schema + fmt::Display + FromStr,
+    T::Err: fmt::Display,
+{
+    fn schema() -> oa::Schema {
+        let inner = T::schema();
+        let pattern = T::string_pattern().or(match &inner.schema_kind {
+            oa::SchemaKind::Type(oa::Type::Integer(int)) if int.minimum >= Some(0) => {
+                Some("^[0-9]+$")
+            }
+            oa::SchemaKind::Type(oa::Type::Integer(_)) => Some("^-?[0-9]+$"),
+            oa::SchemaKind::Type(oa::Type::Number(_)) => {
+                Some("^-?[0-9]+(\\.[0-9]+)?([eE][-+]?[0-9]+)?$")
+            }
+            _ => None,
+        });
+        let example = inner
+            .schema_data
+            .example
+            .and_then(|example| serde_json::from_value::<T>(example).ok())
+            .map(|value| serde_json::Value::String(value.to_string()));
+
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: inner
+                    .schema_data
+                    .title
+                    .map(|title| format!("AsString<{}>", title)),
+                description: Some("Value encoded as a string".into()),
+                example,
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                pattern: pattern.map(Into::into),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+#[test]
+fn parse_example_as_string_bigint() {
+    use num_bigint::{BigInt, BigUint};
+
+    crate::schema::parse_example::<AsString<BigInt>>();
+    crate::schema::parse_example::<AsString<BigUint>>();
+
+    let schema = AsString::<BigInt>::schema();
+    assert_eq!(
+        schema.schema_data.example,
+        Some(serde_json::json!("123456789012345678901234567890"))
+    );
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => {
+            assert_eq!(string.pattern.as_deref(), Some("^-?[0-9]+$"))
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+
+    let big: AsString<BigInt> = serde_json::from_str("\"-98765432109876543210\"").unwrap();
+    assert_eq!(
+        serde_json::to_string(&big).unwrap(),
+        "\"-98765432109876543210\""
+    );
+}
+
+#[test]
+fn parse_example_as_string_i64() {
+    crate::schema::parse_example::<AsString<i64>>();
+
+    match AsString::<i64>::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => {
+            assert_eq!(string.pattern.as_deref(), Some("^-?[0-9]+$"))
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn as_string_round_trip() {
+    let id = AsString(i64::MAX);
+    let encoded = serde_json::to_value(id).unwrap();
+    assert_eq!(encoded, serde_json::json!("9223372036854775807"));
+
+    let decoded: AsString<i64> = serde_json::from_value(encoded).unwrap();
+    assert_eq!(decoded, id);
+
+    assert!(serde_json::from_str::<AsString<i64>>("\"12a\"").is_err());
+}
+
+/// Byte container serialized as the base64 string.
+///
+/// Note that the serde_json serializes the byte containers like `Vec<u8>`,
+/// `Cow<[u8]>` or `Bytes` as an array of integers.
+/// Wrap them with this type to transmit them as the `format: byte` string instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Base64<T = Vec<u8>>(pub T);
+
+/// Bytes transmitted as the base64 string, where the plain `Vec<u8>`
+/// is an array of integers both on the wire and in its schema.
+pub type Base64Bytes = Base64<Vec<u8>>;
+
+impl<T: AsRef<[u8]>> Serialize for Base64<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&STANDARD.encode(self.0.as_ref()))
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Base64<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl<'de, T: From<Vec<u8>>> Deserialize<'de> for Base64<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Base64Visitor<T>(PhantomData<T>);
+
+        impl<'de, T: From<Vec<u8>>> Visitor<'de> for Base64Visitor<T> {
+            type Value = Base64<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("base64 encoded string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                let bytes = STANDARD.decode(v).map_err(E::custom)?;
+                Ok(Base64(bytes.into()))
+            }
+        }
+
+        deserializer.deserialize_str(Base64Visitor(PhantomData))
+    }
+}
+
+impl<T> Schema for Base64<T>
+where
+    T: AsRef<[u8]> + From<Vec<u8>> + 'static,
+{
+    fn schema() -> oa::Schema {
+        byte_schema("Base64")
+    }
+}
+
+#[test]
+fn parse_example_base64() {
+    use std::borrow::Cow;
+
+    crate::schema::parse_example::<Base64<Vec<u8>>>();
+    crate::schema::parse_example::<Base64<Cow<'static, [u8]>>>();
+    crate::schema::parse_example::<Base64<bytes::Bytes>>();
+
+    let schema = Base64Bytes::schema();
+    match schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(ty)) => {
+            assert_eq!(
+                ty.format,
+                oa::VariantOrUnknownOrEmpty::Item(oa::StringFormat::Byte)
+            );
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+#[test]
+fn base64_serde_repr() {
+    let raw = b"foobar".to_vec();
+    assert_eq!(
+        serde_json::to_value(&raw).unwrap(),
+        serde_json::json!([102, 111, 111, 98, 97, 114]),
+    );
+
+    let encoded = serde_json::to_value(Base64(raw.clone())).unwrap();
+    assert_eq!(encoded, serde_json::json!("Zm9vYmFy"));
+
+    let decoded: Base64 = serde_json::from_value(encoded).unwrap();
+    assert_eq!(decoded.0, raw);
+}
+
+/// Geographic coordinate in degrees.
+///
+/// Deserializing rejects the latitude out of `[-90, 90]` and the longitude out of `[-180, 180]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, serde::Deserialize)]
+#[serde(try_from = "RawLatLng")]
+pub struct LatLng {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(serde::Deserialize)]
+struct RawLatLng {
+    lat: f64,
+    lng: f64,
+}
+
+impl TryFrom<RawLatLng> for LatLng {
+    type Error = String;
+
+    fn try_from(raw: RawLatLng) -> Result<Self, Self::Error> {
+        if !(-90.0..=90.0).contains(&raw.lat) {
+            return Err(format!("latitude {} is out of [-90, 90]", raw.lat));
+        }
+        if !(-180.0..=180.0).contains(&raw.lng) {
+            return Err(format!("longitude {} is out of [-180, 180]", raw.lng));
+        }
+
+        Ok(LatLng {
+            lat: raw.lat,
+            lng: raw.lng,
+        })
+    }
+}
+
+impl Schema for LatLng {
+    fn schema() -> oa::Schema {
+        let degree = |title: &str, limit: f64| {
+            oa::ReferenceOr::Item(Box::new(oa::Schema {
+                schema_data: oa::SchemaData {
+                    title: Some(title.into()),
+                    ..Default::default()
+                },
+                schema_kind: oa::SchemaKind::Type(oa::Type::Number(oa::NumberType {
+                    format: oa::VariantOrUnknownOrEmpty::Item(oa::NumberFormat::Double),
+                    minimum: Some(-limit),
+                    maximum: Some(limit),
+                    ..Default::default()
+                })),
+            }))
+        };
+
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("LatLng".into()),
+                description: Some("Geographic coordinate in degrees".into()),
+                example: Some(serde_json::json!({ "lat": 37.5665, "lng": 126.978 })),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+                properties: vec![
+                    ("lat".into(), degree("latitude", 90.0)),
+                    ("lng".into(), degree("longitude", 180.0)),
+                ]
+                .into_iter()
+                .collect(),
+                required: vec!["lat".into(), "lng".into()],
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[test]
+fn parse_example_lat_lng() {
+    crate::schema::parse_example::<LatLng>();
+}
+
+#[test]
+fn lat_lng_round_trip() {
+    let seoul = LatLng {
+        lat: 37.5665,
+        lng: 126.978,
+    };
+    let encoded = serde_json::to_string(&seoul).unwrap();
+    assert_eq!(encoded, r#"{"lat":37.5665,"lng":126.978}"#);
+    assert_eq!(serde_json::from_str::<LatLng>(&encoded).unwrap(), seoul);
+
+    assert!(serde_json::from_str::<LatLng>(r#"{"lat":91.0,"lng":0.0}"#).is_err());
+    assert!(serde_json::from_str::<LatLng>(r#"{"lat":0.0,"lng":-180.5}"#).is_err());
+}
+
+/// Amount of money in the minor unit of the currency, like the cents of the `USD`,
+/// which avoids the rounding errors of the floats.
+///
+/// The currency is always the three uppercase letters of the ISO 4217.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, serde::Deserialize)]
+#[serde(try_from = "RawMoney")]
+pub struct Money {
+    amount: i64,
+    currency: String,
+}
+
+impl Money {
+    /// Returns `None` if the `currency` isn't an ISO 4217 code.
+    pub fn new(amount: i64, currency: impl Into<String>) -> Option<Self> {
+        let currency = currency.into();
+        if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+            return None;
+        }
+
+        Some(Money { amount, currency })
+    }
+
+    pub fn amount(&self) -> i64 {
+        self.amount
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RawMoney {
+    amount: i64,
+    currency: String,
+}
+
+impl TryFrom<RawMoney> for Money {
+    type Error = String;
+
+    fn try_from(raw: RawMoney) -> Result<Self, Self::Error> {
+        let message = format!("currency {:?} is not an ISO 4217 code", raw.currency);
+        Money::new(raw.amount, raw.currency).ok_or(message)
+    }
+}
+
+impl Schema for Money {
+    fn schema() -> oa::Schema {
+        let mut amount = i64::schema();
+        amount.schema_data.title = Some("amount".into());
+        amount.schema_data.description = Some("Amount in the minor unit of the currency".into());
+
+        let currency = oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("currency".into()),
+                description: Some("ISO 4217 currency code".into()),
+                example: Some(serde_json::json!("USD")),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                pattern: Some("^[A-Z]{3}$".into()),
+                min_length: Some(3),
+                max_length: Some(3),
+                ..Default::default()
+            })),
+        };
+
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("Money".into()),
+                description: Some("Amount of money in the minor unit of the currency".into()),
+                example: Some(serde_json::json!({ "amount": 1999, "currency": "USD" })),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+                properties: vec![
+                    ("amount".into(), oa::ReferenceOr::Item(Box::new(amount))),
+                    ("currency".into(), oa::ReferenceOr::Item(Box::new(currency))),
+                ]
+                .into_iter()
+                .collect(),
+                required: vec!["amount".into(), "currency".into()],
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[test]
+fn parse_example_money() {
+    crate::schema::parse_example::<Money>();
+}
+
+#[test]
+fn money_round_trip() {
+    let price = Money::new(1999, "USD").unwrap();
+    assert_eq!((price.amount(), price.currency()), (1999, "USD"));
+    let encoded = serde_json::to_string(&price).unwrap();
+    assert_eq!(encoded, r#"{"amount":1999,"currency":"USD"}"#);
+    assert_eq!(serde_json::from_str::<Money>(&encoded).unwrap(), price);
+
+    for currency in &["usd", "US", "USDT", "U$D"] {
+        assert_eq!(Money::new(1, *currency), None);
+        let encoded = serde_json::json!({ "amount": 1, "currency": currency });
+        assert!(serde_json::from_value::<Money>(encoded).is_err());
+    }
+}
+
+/// String with the length in characters within `MIN..=MAX`.
+///
+/// Deserializing the string out of the bounds fails,
+/// which is reported as the [`InvalidParameter`](crate::error::InvalidParameter).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct BoundedString<const MIN: usize, const MAX: usize>(String);
+
+impl<const MIN: usize, const MAX: usize> BoundedString<MIN, MAX> {
+    /// Returns `None` if the length of the `value` is out of the bounds.
+    pub fn new(value: impl Into<String>) -> Option<Self> {
+        let value = value.into();
+        let len = value.chars().count();
+
+        if (MIN..=MAX).contains(&len) {
+            Some(BoundedString(value))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// The empty string, only for the bounds which allow it.
+impl<const MAX: usize> Default for BoundedString<0, MAX> {
+    fn default() -> Self {
+        BoundedString(String::new())
+    }
+}
+
+impl<'de, const MIN: usize, const MAX: usize> Deserialize<'de> for BoundedString<MIN, MAX> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        let len = value.chars().count();
+
+        BoundedString::new(value).ok_or_else(|| {
+            de::Error::invalid_length(len, &format!("length in {}..={}", MIN, MAX).as_str())
+        })
+    }
+}
+
+impl<const MIN: usize, const MAX: usize> Schema for BoundedString<MIN, MAX> {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("BoundedString".into()),
+                description: Some(format!("String of {} to {} characters", MIN, MAX)),
+                example: Some("x".repeat(MIN.max(1).min(MAX)).into()),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                min_length: Some(MIN),
+                max_length: Some(MAX),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[test]
+fn parse_example_bounded_string() {
+    crate::schema::parse_example::<BoundedString<3, 16>>();
+    crate::schema::parse_example::<BoundedString<0, 0>>();
+}
+
+#[test]
+fn bounded_string_validates_length() {
+    type Username = BoundedString<3, 8>;
+
+    let name: Username = serde_json::from_str(r#""ferris""#).unwrap();
+    assert_eq!(name.as_str(), "ferris");
+    assert_eq!(BoundedString::<0, 8>::default().as_str(), "");
+    assert!(serde_json::from_str::<Username>(r#""al""#).is_err());
+
+    let err =
+        crate::codec::from_json::<Vec<Username>>(r#"["ferris", "way too long"]"#).unwrap_err();
+    match err {
+        crate::BaseError::InvalidParameter { body, .. } => assert_eq!(body[0].name, "[1]"),
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    match Username::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => {
+            assert_eq!(string.min_length, Some(3));
+            assert_eq!(string.max_length, Some(8));
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+/// Array with at least one element.
+///
+/// Deserializing the empty array fails,
+/// which is reported as the [`InvalidParameter`](crate::error::InvalidParameter).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct NonEmpty<T>(Vec<T>);
+
+impl<T> NonEmpty<T> {
+    /// Returns `None` if the `vec` is empty.
+    pub fn new(vec: Vec<T>) -> Option<Self> {
+        if vec.is_empty() {
+            None
+        } else {
+            Some(NonEmpty(vec))
+        }
+    }
+
+    pub fn first(&self) -> &T {
+        &self.0[0]
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for NonEmpty<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let vec = Vec::deserialize(deserializer)?;
+        NonEmpty::new(vec).ok_or_else(|| de::Error::invalid_length(0, &"at least one element"))
+    }
+}
+
+impl<T: Schema> Schema for NonEmpty<T> {
+    fn schema() -> oa::Schema {
+        let mut schema = Vec::<T>::schema();
+        schema.schema_data.title = Some("NonEmpty".into());
+        schema.schema_data.description = Some("Array with at least one element".into());
+        schema.schema_data.example = T::schema()
+            .schema_data
+            .example
+            .map(|example| serde_json::json!([example]));
+
+        if let oa::SchemaKind::Type(oa::Type::Array(array)) = &mut schema.schema_kind {
+            array.min_items = Some(1);
+        }
+
+        schema
+    }
+
+    fn register_components(registry: &mut crate::schema::SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+#[test]
+fn parse_example_non_empty() {
+    crate::schema::parse_example::<NonEmpty<u32>>();
+}
+
+#[test]
+fn non_empty_rejects_empty_array() {
+    let tags: NonEmpty<String> = serde_json::from_str(r#"["rust"]"#).unwrap();
+    assert_eq!(tags.first(), "rust");
+
+    let err = crate::codec::from_json::<NonEmpty<String>>("[]").unwrap_err();
+    assert!(matches!(err, crate::BaseError::InvalidParameter { .. }));
+
+    match NonEmpty::<String>::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Array(array)) => assert_eq!(array.min_items, Some(1)),
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+/// Value with at most `MAX` bytes, like the `String` or the [`Base64`] blob.
+///
+/// The size is checked on the decoded bytes, so the `Base64<Vec<u8>>` counts
+/// the bytes after the base64 decoding. Deserializing the larger value fails,
+/// which is reported as the [`InvalidParameter`](crate::error::InvalidParameter)
+/// naming the field even if the whole body is within the `max_request_length`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct MaxBytes<T, const MAX: usize>(T);
+
+impl<T: AsRef<[u8]>, const MAX: usize> MaxBytes<T, MAX> {
+    /// Returns `None` if the `value` is larger than `MAX` bytes.
+    pub fn new(value: T) -> Option<Self> {
+        if value.as_ref().len() <= MAX {
+            Some(MaxBytes(value))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, const MAX: usize> MaxBytes<T, MAX> {
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'de, T, const MAX: usize> Deserialize<'de> for MaxBytes<T, MAX>
+where
+    T: AsRef<[u8]> + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = T::deserialize(deserializer)?;
+        let len = value.as_ref().len();
+
+        MaxBytes::new(value).ok_or_else(|| {
+            de::Error::invalid_length(len, &format!("at most {} bytes", MAX).as_str())
+        })
+    }
+}
+
+impl<T: Schema + AsRef<[u8]>, const MAX: usize> Schema for MaxBytes<T, MAX> {
+    fn schema() -> oa::Schema {
+        let mut schema = T::schema();
+        let limit = format!("At most {} bytes", MAX);
+        schema.schema_data.description = Some(match schema.schema_data.description {
+            Some(description) => format!("{}. {}", description, limit),
+            None => limit,
+        });
+        schema
+    }
+
+    fn register_components(registry: &mut crate::schema::SchemaRegistry) {
+        T::register_components(registry);
+    }
+}
+
+#[test]
+fn parse_example_max_bytes() {
+    crate::schema::parse_example::<MaxBytes<Base64, 16>>();
+    crate::schema::parse_example::<MaxBytes<String, 16>>();
+}
+
+#[test]
+fn max_bytes_names_oversized_field() {
+    #[derive(Debug, serde::Deserialize)]
+    struct Upload {
+        name: MaxBytes<String, 16>,
+        avatar: MaxBytes<Base64, 4>,
+    }
+
+    let upload = r#"{"name": "ferris", "avatar": "Zm9v"}"#;
+    let upload = crate::codec::from_json::<Upload>(upload).unwrap();
+    assert_eq!(upload.name.get(), "ferris");
+    assert_eq!(upload.avatar.into_inner().0, b"foo");
+
+    let upload = r#"{"name": "ferris", "avatar": "Zm9vYmFy"}"#;
+    match crate::codec::from_json::<Upload>(upload).unwrap_err() {
+        crate::BaseError::InvalidParameter { body, .. } => assert_eq!(body[0].name, "avatar"),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+/// Successful response wrapped as `{"data": T}`.
+///
+/// Pair it with the [`ErrorEnvelope`] to keep the same structure across the service.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, serde::Deserialize)]
+pub struct Envelope<T> {
+    pub data: T,
+}
+
+impl<T: Schema> Schema for Envelope<T> {
+    fn schema() -> oa::Schema {
+        envelope_schema("Envelope", "data", T::schema_name(), T::schema())
+    }
+
+    fn register_components(registry: &mut crate::schema::SchemaRegistry) {
+        registry.register::<T>();
+    }
+}
+
+/// Error response wrapped as `{"error": E}`, with the status and the headers of the `E`.
+///
+/// Its [`error_schema`](Error::error_schema) wraps every schema of the `E` the same way.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, serde::Deserialize)]
+pub struct ErrorEnvelope<E> {
+    pub error: E,
+}
+
+impl<E> From<E> for ErrorEnvelope<E> {
+    fn from(error: E) -> Self {
+        ErrorEnvelope { error }
+    }
+}
+
+impl<E: Schema> Schema for ErrorEnvelope<E> {
+    fn schema() -> oa::Schema {
+        envelope_schema("ErrorEnvelope", "error", E::schema_name(), E::schema())
+    }
+
+    fn register_components(registry: &mut crate::schema::SchemaRegistry) {
+        registry.register::<E>();
+    }
+}
+
+impl<E: Error> Error for ErrorEnvelope<E> {
+    fn status(&self) -> StatusCode {
+        self.error.status()
+    }
+
+    fn headers(&self) -> HeaderMap {
+        self.error.headers()
+    }
+
+    fn error_schema() -> ErrorSchema {
+        let wrap = |schema: oa::Schema| envelope_schema("ErrorEnvelope", "error", None, schema);
+        let inner = E::error_schema();
+
+        ErrorSchema {
+            default_schema: inner.default_schema.map(wrap),
+            schemas: inner
+                .schemas
+                .into_iter()
+                .map(|(status, schema)| (status, wrap(schema)))
+                .collect(),
+        }
+    }
+}
+
+/// Object with the single required `field` of the `schema`,
+/// which is referred if the `name` is given.
+fn envelope_schema(
+    title: &str,
+    field: &str,
+    name: Option<std::borrow::Cow<'static, str>>,
+    schema: oa::Schema,
+) -> oa::Schema {
+    let example = schema
+        .schema_data
+        .example
+        .clone()
+        .map(|example| serde_json::json!({ field: example }));
+    let property = match name {
+        Some(name) => oa::ReferenceOr::ref_(&format!("#/components/schemas/{}", name)),
+        None => oa::ReferenceOr::Item(Box::new(schema)),
+    };
+
+    oa::Schema {
+        schema_data: oa::SchemaData {
+            title: Some(title.into()),
+            example,
+            ..Default::default()
+        },
+        schema_kind: oa::SchemaKind::Type(oa::Type::Object(oa::ObjectType {
+            properties: Some((field.into(), property)).into_iter().collect(),
+            required: vec![field.into()],
+            ..Default::default()
+        })),
+    }
+}
+
+#[test]
+fn parse_example_envelope() {
+    crate::schema::parse_example::<Envelope<u32>>();
+    crate::schema::parse_example::<ErrorEnvelope<String>>();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn envelope_wraps_responses() {
+    use std::sync::Arc;
+
+    use crate::method::SupportedMethod;
+    use crate::BaseError;
+
+    async fn find(_app: Arc<()>, id: u32) -> Result<Envelope<u32>, ErrorEnvelope<BaseError>> {
+        match id {
+            1 => Ok(Envelope { data: 42 }),
+            _ => Err(BaseError::NotFound.into()),
+        }
+    }
+
+    let router = crate::Router::new(Arc::new(())).route(SupportedMethod::Post, "/find", find);
+
+    let req = http::Request::post("/find").body(Ok("1")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), r#"{"data":42}"#);
+
+    let req = http::Request::post("/find").body(Ok("2")).unwrap();
+    let resp = router.call(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.body(), r#"{"error":"NotFound"}"#);
+
+    let route = &router.routes[0];
+    match &route.response_schema.schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            assert_eq!(object.required, ["data"]);
+            assert_eq!(
+                object.properties["data"],
+                oa::ReferenceOr::Item(Box::new(u32::schema()))
+            );
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+    match &route.error_schema.schemas[&StatusCode::NOT_FOUND].schema_kind {
+        oa::SchemaKind::Type(oa::Type::Object(object)) => {
+            assert_eq!(object.required, ["error"])
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+    assert!(route.error_schema.default_schema.is_some());
+}
+
+/// Regular expression which the [`Pattern`] string should match.
+///
+/// Like the JSON Schema's `pattern`, the string is searched for the match,
+/// so anchor it with the `^` and `$` to match the whole string.
+#[cfg(feature = "regex")]
+pub trait PatternRule: 'static {
+    const TITLE: &'static str;
+    const PATTERN: &'static str;
+    /// Matching string used as the example of the schema.
+    const EXAMPLE: &'static str;
+}
+
+/// String which matches the regular expression of the `P`.
+///
+/// Deserializing the string which doesn't match fails,
+/// which is reported as the [`InvalidParameter`](crate::error::InvalidParameter).
+/// The expression is documented as the `pattern` of the schema.
+#[cfg(feature = "regex")]
+pub struct Pattern<P>(String, PhantomData<P>);
+
+#[cfg(feature = "regex")]
+impl<P: PatternRule> Pattern<P> {
+    /// Returns `None` if the `value` doesn't match the pattern.
+    pub fn new(value: impl Into<String>) -> Option<Self> {
+        let value = value.into();
+
+        if pattern_regex(P::PATTERN).is_match(&value) {
+            Some(Pattern(value, PhantomData))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<P> Pattern<P> {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// Compiles the `pattern` once and returns the cached one afterwards.
+#[cfg(feature = "regex")]
+fn pattern_regex(pattern: &'static str) -> regex::Regex {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    static CACHE: OnceLock<Mutex<HashMap<&'static str, regex::Regex>>> = OnceLock::new();
+
+    CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(pattern)
+        .or_insert_with(|| {
+            regex::Regex::new(pattern)
+                .unwrap_or_else(|err| panic!("invalid pattern {:?}: {}", pattern, err))
+        })
+        .clone()
+}
+
+#[cfg(feature = "regex")]
+impl<P> fmt::Debug for Pattern<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Pattern").field(&self.0).finish()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<P> Clone for Pattern<P> {
+    fn clone(&self) -> Self {
+        Pattern(self.0.clone(), PhantomData)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<P> PartialEq for Pattern<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<P> Eq for Pattern<P> {}
+
+#[cfg(feature = "regex")]
+impl<P> std::hash::Hash for Pattern<P> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<P> Serialize for Pattern<P> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<'de, P: PatternRule> Deserialize<'de> for Pattern<P> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        Pattern::new(value).ok_or_else(|| {
+            de::Error::custom(format!(
+                "{} doesn't match the pattern {}",
+                P::TITLE,
+                P::PATTERN
+            ))
+        })
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<P: PatternRule> Schema for Pattern<P> {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some(P::TITLE.into()),
+                example: Some(P::EXAMPLE.into()),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                pattern: Some(P::PATTERN.into()),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+/// Rule of the [`Email`], which only checks the overall shape of the address.
+#[cfg(feature = "regex")]
+#[derive(Debug)]
+pub enum EmailRule {}
+
+#[cfg(feature = "regex")]
+impl PatternRule for EmailRule {
+    const TITLE: &'static str = "Email";
+    const PATTERN: &'static str = r"^[^@\s]+@[^@\s]+\.[^@\s]+$";
+    const EXAMPLE: &'static str = "ferris@example.com";
+}
+
+/// Email address like `ferris@example.com`.
+#[cfg(feature = "regex")]
+pub type Email = Pattern<EmailRule>;
+
+#[cfg(feature = "regex")]
+#[test]
+fn parse_example_email() {
+    crate::schema::parse_example::<Email>();
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn pattern_validates_email() {
+    let email: Email = serde_json::from_str(r#""ferris@example.com""#).unwrap();
+    assert_eq!(email.as_str(), "ferris@example.com");
+    assert!(Email::new("ferris at example.com").is_none());
+
+    let err = crate::codec::from_json::<Vec<Email>>(r#"["a@b.io", "not an email"]"#).unwrap_err();
+    match err {
+        crate::BaseError::InvalidParameter { body, .. } => assert_eq!(body[0].name, "[1]"),
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    match Email::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::String(string)) => {
+            assert_eq!(string.pattern.as_deref(), Some(EmailRule::PATTERN))
+        }
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
+
+/// IP network in the CIDR notation, like the `"10.0.0.0/8"` or the `"2001:db8::/32"`.
+///
+/// The bits of the address after the prefix are kept as given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Returns `None` if the `prefix` is longer than the address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            return None;
+        }
+
+        Some(Cidr { addr, prefix })
+    }
+
+    /// Returns `None` if the `text` is not in the CIDR notation.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (addr, prefix) = text.split_once('/')?;
+        if prefix.is_empty() || prefix.len() > 3 || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        Cidr::new(addr.parse().ok()?, prefix.parse().ok()?)
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether the `ip` is within the network. Addresses of the other family are not.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl Serialize for Cidr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+
+        Cidr::parse(&text).ok_or_else(|| {
+            de::Error::invalid_value(de::Unexpected::Str(&text), &"IP network in CIDR notation")
+        })
+    }
+}
+
+/// Loose pattern of the IPv4 and the IPv6 networks, the addresses are checked on parsing.
+const CIDR_PATTERN: &str =
+    r"^([0-9]{1,3}(\.[0-9]{1,3}){3}/[0-9]{1,2}|[0-9A-Fa-f:.]*:[0-9A-Fa-f:.]*/[0-9]{1,3})$";
+
+impl Schema for Cidr {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("Cidr".into()),
+                description: Some("IP network in the CIDR notation".into()),
+                example: Some("10.0.0.0/8".into()),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                pattern: Some(CIDR_PATTERN.into()),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[test]
+fn parse_example_cidr() {
+    crate::schema::parse_example::<Cidr>();
+}
+
+#[test]
+fn cidr_rejects_malformed_networks() {
+    let net = Cidr::parse("10.0.0.0/8").unwrap();
+    assert!(net.contains([10, 1, 2, 3].into()));
+    assert!(!net.contains([11, 0, 0, 0].into()));
+    assert!(Cidr::parse("0.0.0.0/0")
+        .unwrap()
+        .contains([8, 8, 8, 8].into()));
+
+    let net: Cidr = serde_json::from_str(r#""2001:db8::/32""#).unwrap();
+    assert_eq!(net.prefix(), 32);
+    assert_eq!(serde_json::to_string(&net).unwrap(), r#""2001:db8::/32""#);
+    assert!(net.contains("2001:db8:1::1".parse().unwrap()));
+    assert!(!net.contains([10, 0, 0, 1].into()));
+
+    for text in [
+        "10.0.0.0",
+        "10.0.0.0/33",
+        "10.0.0/8",
+        "::/129",
+        "10.0.0.0/+8",
+        "10.0.0.0/",
+        "/8",
+    ] {
+        assert_eq!(Cidr::parse(text), None, "{:?}", text);
+    }
+
+    let err = crate::codec::from_json::<Vec<Cidr>>(r#"["10.0.0.0/8", "10.0.0.0/33"]"#).unwrap_err();
+    match err {
+        crate::BaseError::InvalidParameter { body, .. } => assert_eq!(body[0].name, "[1]"),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+/// `Duration` serialized as the ISO 8601 duration string like `"PT1H30M"`.
+///
+/// Only the exact units are accepted, i.e. weeks, days, hours, minutes
+/// and the seconds with up to 9 fractional digits. Years and months are rejected.
+/// It's always serialized with the hours, minutes and seconds.
+#[cfg(feature = "iso8601_duration")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IsoDuration(pub std::time::Duration);
+
+#[cfg(feature = "iso8601_duration")]
+impl IsoDuration {
+    /// Returns `None` if the `text` is not a supported ISO 8601 duration.
+    pub fn parse(text: &str) -> Option<Self> {
+        let rest = text.strip_prefix('P')?;
+        let (date, time) = match rest.split_once('T') {
+            Some((_, "")) => return None,
+            Some((date, time)) => (date, time),
+            None if rest.is_empty() => return None,
+            None => (rest, ""),
+        };
+
+        let mut secs = 0;
+        let mut nanos = 0;
+        parse_duration_part(
+            date,
+            &[('W', 604_800), ('D', 86_400)],
+            &mut secs,
+            &mut nanos,
+        )?;
+        parse_duration_part(
+            time,
+            &[('H', 3_600), ('M', 60), ('S', 1)],
+            &mut secs,
+            &mut nanos,
+        )?;
+
+        Some(IsoDuration(std::time::Duration::new(secs, nanos)))
+    }
+}
+
+/// Accumulates the components of the `part`, which must appear in the order of the `units`.
+/// Only the seconds may have the fraction.
+#[cfg(feature = "iso8601_duration")]
+fn parse_duration_part(
+    mut part: &str,
+    units: &[(char, u64)],
+    secs: &mut u64,
+    nanos: &mut u32,
+) -> Option<()> {
+    let mut units = units.iter();
+
+    while !part.is_empty() {
+        let end = part.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (number, rest) = part.split_at(end);
+        let designator = rest.chars().next()?;
+        part = &rest[designator.len_utf8()..];
+
+        let &(_, unit) = units.by_ref().find(|(name, _)| *name == designator)?;
+        let (whole, fraction) = match number.split_once('.') {
+            Some((whole, fraction)) if designator == 'S' => (whole, Some(fraction)),
+            Some(_) => return None,
+            None => (number, None),
+        };
+
+        *secs = secs.checked_add(whole.parse::<u64>().ok()?.checked_mul(unit)?)?;
+
+        if let Some(fraction) = fraction {
+            if fraction.is_empty()
+                || fraction.len() > 9
+                || !fraction.bytes().all(|b| b.is_ascii_digit())
+            {
+                return None;
+            }
+            *nanos = format!("{:0<9}", fraction).parse().ok()?;
+        }
+    }
+
+    Some(())
+}
+
+#[cfg(feature = "iso8601_duration")]
+impl fmt::Display for IsoDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let nanos = self.0.subsec_nanos();
+
+        if secs == 0 && nanos == 0 {
+            return f.write_str("PT0S");
+        }
+
+        f.write_str("PT")?;
+        if secs >= 3_600 {
+            write!(f, "{}H", secs / 3_600)?;
+        }
+        if secs % 3_600 >= 60 {
+            write!(f, "{}M", secs % 3_600 / 60)?;
+        }
+        let seconds = secs % 60;
+        if seconds != 0 || nanos != 0 {
+            write!(f, "{}", seconds)?;
+            if nanos != 0 {
+                let fraction = format!("{:09}", nanos);
+                write!(f, ".{}", fraction.trim_end_matches('0'))?;
+            }
+            f.write_str("S")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "iso8601_duration")]
+impl Serialize for IsoDuration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "iso8601_duration")]
+impl<'de> Deserialize<'de> for IsoDuration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+
+        IsoDuration::parse(&text).ok_or_else(|| {
+            de::Error::invalid_value(de::Unexpected::Str(&text), &"ISO 8601 duration")
+        })
+    }
+}
+
+#[cfg(feature = "iso8601_duration")]
+impl Schema for IsoDuration {
+    fn schema() -> oa::Schema {
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("IsoDuration".into()),
+                description: Some("ISO 8601 duration".into()),
+                example: Some("PT1H30M".into()),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                format: oa::VariantOrUnknownOrEmpty::Unknown("duration".into()),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[cfg(feature = "iso8601_duration")]
+#[test]
+fn parse_example_iso_duration() {
+    crate::schema::parse_example::<IsoDuration>();
+}
+
+#[cfg(feature = "iso8601_duration")]
+#[test]
+fn iso_duration_round_trip() {
+    use std::time::Duration;
+
+    for (text, duration) in [
+        ("PT0S", Duration::ZERO),
+        ("PT1H30M", Duration::from_secs(5_400)),
+        ("PT0.5S", Duration::from_millis(500)),
+        ("PT1M0.000000001S", Duration::new(60, 1)),
+        ("PT26H3.25S", Duration::new(93_603, 250_000_000)),
+    ] {
+        let json = serde_json::to_string(&IsoDuration(duration)).unwrap();
+        assert_eq!(json, format!("\"{}\"", text));
+        assert_eq!(
+            serde_json::from_str::<IsoDuration>(&json).unwrap().0,
+            duration
+        );
+    }
+
+    assert_eq!(
+        IsoDuration::parse("P1W1DT0.5S"),
+        Some(IsoDuration(Duration::new(691_200, 500_000_000)))
+    );
+
+    for text in [
+        "", "P", "PT", "P1Y", "P1M", "PT1.5M", "PT1S2M", "1H", "PT.5S",
+    ] {
+        assert_eq!(IsoDuration::parse(text), None, "{:?}", text);
+    }
+}
+
+/// Set of `bitflags` flags, serialized as an array of the names of the set flags.
+///
+/// The names are taken from the `bitflags!` declaration,
+/// like the `SupportedMethod` is represented with its variant names.
+#[cfg(feature = "bitflags")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlagSet<F>(pub F);
+
+#[cfg(feature = "bitflags")]
+impl<F: bitflags::Flags> Serialize for FlagSet<F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter_names().map(|(name, _)| name))
+    }
+}
+
+#[cfg(feature = "bitflags")]
+impl<'de, F: bitflags::Flags> Deserialize<'de> for FlagSet<F> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let names = Vec::<std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        let mut flags = F::empty();
+
+        for name in names {
+            let flag = F::from_name(&name)
+                .ok_or_else(|| de::Error::custom(format!("unknown flag {}", name)))?;
+            flags.insert(flag);
+        }
+
+        Ok(FlagSet(flags))
+    }
+}
+
+#[cfg(feature = "bitflags")]
+impl<F: bitflags::Flags + 'static> Schema for FlagSet<F> {
+    fn schema() -> oa::Schema {
+        let names: Vec<String> = F::FLAGS
+            .iter()
+            .filter(|flag| flag.is_named())
+            .map(|flag| flag.name().to_owned())
+            .collect();
+
+        oa::Schema {
+            schema_data: oa::SchemaData {
+                title: Some("FlagSet".into()),
+                description: Some("Set of flags".into()),
+                example: Some(serde_json::json!(names.iter().take(1).collect::<Vec<_>>())),
+                ..Default::default()
+            },
+            schema_kind: oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType {
+                items: oa::ReferenceOr::Item(Box::new(oa::Schema {
+                    schema_data: Default::default(),
+                    schema_kind: oa::SchemaKind::Type(oa::Type::String(oa::StringType {
+                        enumeration: names,
+                        ..Default::default()
+                    })),
+                })),
+                min_items: None,
+                max_items: None,
+                unique_items: true,
+            })),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "bitflags"))]
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Permission: u8 {
+        const READ = 0b001;
+        const WRITE = 0b010;
+        const EXEC = 0b100;
+    }
+}
+
+#[cfg(feature = "bitflags")]
+#[test]
+fn flag_set_round_trip() {
+    crate::schema::parse_example::<FlagSet<Permission>>();
+
+    let flags = FlagSet(Permission::READ | Permission::EXEC);
+    let encoded = serde_json::to_value(flags).unwrap();
+    assert_eq!(encoded, serde_json::json!(["READ", "EXEC"]));
+
+    let decoded: FlagSet<Permission> = serde_json::from_value(encoded).unwrap();
+    assert_eq!(decoded, flags);
+    assert!(serde_json::from_str::<FlagSet<Permission>>(r#"["DELETE"]"#).is_err());
+
+    match FlagSet::<Permission>::schema().schema_kind {
+        oa::SchemaKind::Type(oa::Type::Array(array)) => match array.items {
+            oa::ReferenceOr::Item(item) => match item.schema_kind {
+                oa::SchemaKind::Type(oa::Type::String(string)) => {
+                    assert_eq!(string.enumeration, ["READ", "WRITE", "EXEC"])
+                }
+                other => panic!("unexpected item {:?}", other),
+            },
+            other => panic!("unexpected item {:?}", other),
+        },
+        other => panic!("unexpected schema {:?}", other),
+    }
+}
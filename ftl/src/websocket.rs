@@ -0,0 +1,141 @@
+//! WebSocket endpoints alongside the request/response handlers.
+//!
+//! The handler answers the upgrade request with the response returned by the [`upgrade`],
+//! and the connection is handed to the callback once the handshake completes.
+//!
+//! ```ignore
+//! fn chat(_app: Arc<App>, mut req: Request<...>) -> BoxFuture<...> {
+//!     let resp = websocket::upgrade(&mut req, |mut socket| async move {
+//!         while let Some(Ok(msg)) = socket.next().await {
+//!             if socket.send(msg).await.is_err() {
+//!                 break;
+//!             }
+//!         }
+//!     });
+//!
+//!     Box::pin(async move { Ok(resp.unwrap_or_else(|err| error_response(&err))) })
+//! }
+//! ```
+
+use std::future::Future;
+
+use http::header::{self, HeaderName, HeaderValue};
+use http::{Request, Response, StatusCode};
+use hyper::upgrade::Upgraded;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+pub use tokio_tungstenite::tungstenite::Message;
+
+use crate::error::{BaseError, DynError};
+
+/// Server side of the WebSocket connection.
+pub type WebSocket = tokio_tungstenite::WebSocketStream<Upgraded>;
+
+/// Returns `true` if the request asks to upgrade the connection to the WebSocket.
+pub fn is_upgrade<B>(req: &Request<B>) -> bool {
+    header_contains(req, header::CONNECTION, "upgrade")
+        && header_contains(req, header::UPGRADE, "websocket")
+}
+
+/// Completes the handshake of the WebSocket upgrade request.
+///
+/// Returns the `101 Switching Protocols` response which the handler should return as is.
+/// The `callback` is spawned with the connection after the response is sent.
+pub fn upgrade<B, F, Fut>(req: &mut Request<B>, callback: F) -> Result<Response<String>, BaseError>
+where
+    F: FnOnce(WebSocket) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    if !is_upgrade(req) {
+        return Err(bad_request("not a WebSocket upgrade request"));
+    }
+    if req.headers().get(header::SEC_WEBSOCKET_VERSION) != Some(&HeaderValue::from_static("13")) {
+        return Err(bad_request("unsupported WebSocket version"));
+    }
+    let accept = match req.headers().get(header::SEC_WEBSOCKET_KEY) {
+        Some(key) => derive_accept_key(key.as_bytes()),
+        None => return Err(bad_request("missing Sec-WebSocket-Key header")),
+    };
+
+    let on_upgrade = hyper::upgrade::on(req);
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let socket = WebSocket::from_raw_socket(upgraded, Role::Server, None).await;
+                callback(socket).await;
+            }
+            Err(err) => tracing::debug!(%err, "WebSocket upgrade failed"),
+        }
+    });
+
+    let mut resp = Response::new(String::new());
+    *resp.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    let headers = resp.headers_mut();
+    headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(
+        header::SEC_WEBSOCKET_ACCEPT,
+        // The base64 is always a valid header value.
+        HeaderValue::from_str(&accept).unwrap(),
+    );
+
+    Ok(resp)
+}
+
+/// Case insensitive search of the `token` within the comma separated header values.
+fn header_contains<B>(req: &Request<B>, name: HeaderName, token: &str) -> bool {
+    req.headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+fn bad_request(reason: &'static str) -> BaseError {
+    DynError::new(StatusCode::BAD_REQUEST, reason).into()
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn websocket_echoes_a_frame() {
+    use std::sync::Arc;
+
+    use futures_util::future::BoxFuture;
+    use futures_util::{SinkExt, StreamExt};
+
+    use crate::error::error_response;
+    use crate::BoxError;
+
+    fn echo(
+        _app: Arc<()>,
+        mut req: Request<Result<&str, Box<BaseError>>>,
+    ) -> BoxFuture<'_, Result<Response<String>, BoxError>> {
+        let resp = upgrade(&mut req, |mut socket| async move {
+            if let Some(Ok(msg)) = socket.next().await {
+                drop(socket.send(msg).await);
+            }
+        });
+
+        Box::pin(async move { Ok(resp.unwrap_or_else(|err| error_response(&err))) })
+    }
+
+    let router = crate::Router::from_handler(Arc::new(()), echo);
+    let (handle, addr) = router.try_run(([127, 0, 0, 1], 0).into()).unwrap();
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut socket, resp) = tokio_tungstenite::client_async(format!("ws://{}/", addr), stream)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+    socket.send(Message::text("ping")).await.unwrap();
+    assert_eq!(socket.next().await.unwrap().unwrap(), Message::text("ping"));
+
+    let uri: http::Uri = format!("http://{}/", addr).parse().unwrap();
+    let resp = hyper::Client::new().get(uri).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    handle.abort();
+}